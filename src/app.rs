@@ -1,37 +1,98 @@
+use crate::config::Config;
+use crate::passes::find_pass;
 use derivative::Derivative;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone)]
-pub struct PassesConfig {
-    pub imports: bool,
-    pub top: bool,
+/// A pass in the pipeline with its options, e.g. `hdd:max-depth=3`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassConfig {
+    pub name: String,
+    pub options: Vec<(String, String)>,
+}
+
+impl PassConfig {
+    /// Returns the value of the option with the given `name` if it is set.
+    pub fn option<T>(&self, name: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+    {
+        match self.options.iter().rev().find(|(n, _)| n == name) {
+            Some((_, value)) => match value.parse::<T>() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(format!("Invalid value of {}: {}", name, value)),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for PassConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (name, value) in self.options.iter() {
+            write!(f, ":{}={}", name, value)?;
+        }
+        Ok(())
+    }
 }
 
 mod args {
     pub const SCRIPT: &str = "SCRIPT";
     pub const FILE: &str = "FILE";
     pub const PASSES: &str = "PASSES";
+    pub const LIST_PASSES: &str = "LIST_PASSES";
+    pub const CONFIG: &str = "CONFIG";
+    pub const DUMP_CONFIG: &str = "DUMP_CONFIG";
     pub const OUTPUT: &str = "OUTPUT";
     pub const TIMEOUT: &str = "TIMEOUT";
+    pub const TIMEOUT_INTERESTING: &str = "TIMEOUT_INTERESTING";
+    pub const CHECK_EMPTY: &str = "CHECK_EMPTY";
+    pub const RETRIES: &str = "RETRIES";
+    pub const CONFIRM: &str = "CONFIRM";
+    pub const EXIT_CODE: &str = "EXIT_CODE";
+    pub const SIGNAL: &str = "SIGNAL";
+    pub const STDOUT_REGEX: &str = "STDOUT_REGEX";
+    pub const STDOUT_NOT_REGEX: &str = "STDOUT_NOT_REGEX";
+    pub const STDERR_REGEX: &str = "STDERR_REGEX";
+    pub const STDERR_NOT_REGEX: &str = "STDERR_NOT_REGEX";
+    pub const RESULT: &str = "RESULT";
+    pub const IN_PLACE: &str = "IN_PLACE";
+    pub const EVENTS: &str = "EVENTS";
+    pub const NO_SUBSETS: &str = "NO_SUBSETS";
+    pub const PERSIST_CACHE: &str = "PERSIST_CACHE";
+    pub const RESUME: &str = "RESUME";
+    pub const JOBS: &str = "JOBS";
+    pub const MAX_ROUNDS: &str = "MAX_ROUNDS";
     pub const FORCE: &str = "FORCE";
     pub const RECURSIVE: &str = "RECURSIVE";
     pub const VERBOSE: &str = "VERBOSE";
+    pub const QUIET: &str = "QUIET";
+    pub const REPLAY: &str = "replay";
+    pub const TRACE: &str = "TRACE";
 }
 
 mod defaults {
-    use super::PassesConfig;
-
     pub const OUTPUT_DIR: &str = "/tmp/dd/";
-    pub const PASSES_CONFIG: PassesConfig = PassesConfig {
-        imports: true,
-        top: true,
-    };
+    pub const CONFIG_FILE: &str = "dd.toml";
+    pub const BEST_FILE: &str = "best.lua";
+    pub const BEST_DIR: &str = "best";
+    pub const CACHE_FILE: &str = "cache";
+    pub const CHECKPOINT_FILE: &str = "checkpoint";
+    pub const TRACE_FILE: &str = "trace.jsonl";
+    pub const SUMMARY_FILE: &str = "summary";
+    pub const SANITY_DIR: &str = "sanity";
+    pub const BACKUP_EXT: &str = "orig";
+    pub const PASSES: &str = "imports,top,hdd";
 }
 
 #[derive(Derivative)]
 #[derivative(Debug, Default(new = "true"))]
 pub struct App {
     #[derivative(Default(value = "String::from(\"\")"))]
-    /// Absolute path to the script that checks failure.
+    /// Absolute path to the script that checks failure or a command line, where `{}` is replaced
+    /// with the path to the candidate.
     pub script: String,
 
     /// Path to file to be tested.
@@ -42,21 +103,124 @@ pub struct App {
     #[derivative(Default(value = "defaults::OUTPUT_DIR.to_string()"))]
     pub output_dir: String,
 
+    /// Path to the file where the reduced program will be written.
+    #[derivative(Default(value = "None"))]
+    pub result: Option<String>,
+
+    /// Replace the tested file with the reduced program keeping the backup of the original one.
+    #[derivative(Default(value = "false"))]
+    pub in_place: bool,
+
+    /// Path to the file where the reduction events are written in the JSON Lines format. `-`
+    /// means the standard output.
+    #[derivative(Default(value = "None"))]
+    pub events: Option<String>,
+
     /// Timeout to script execution.
     #[derivative(Default(value = "None"))]
     pub timeout: Option<u32>,
 
+    /// Consider the candidates for which the script exceeds the timeout as reproducing the
+    /// failure, e.g. to reduce a program that hangs.
+    #[derivative(Default(value = "false"))]
+    pub timeout_interesting: bool,
+
+    /// Check that the script doesn't reproduce the failure for an empty file before the reduction.
+    #[derivative(Default(value = "false"))]
+    pub check_empty: bool,
+
+    /// Number of the additional executions of the script when the outcome is unresolved, e.g.
+    /// the script returned the exit code 125.
+    #[derivative(Default(value = "0"))]
+    pub retries: usize,
+
+    /// Number of the additional executions of the script that must confirm that the candidate
+    /// reproduces the failure. The candidates with different outcomes are skipped as flaky.
+    #[derivative(Default(value = "0"))]
+    pub confirm: usize,
+
+    /// Exit codes of the script that reproduce the failure. If neither exit codes nor the signal
+    /// are set, any non-zero exit code reproduces it, or any exit code if the output of the script
+    /// is matched against the regular expressions.
+    #[derivative(Default(value = "vec![]"))]
+    pub exit_codes: Vec<i32>,
+
+    /// Signal that kills the script when the failure is reproduced.
+    #[derivative(Default(value = "None"))]
+    pub signal: Option<i32>,
+
+    /// Regular expression that must match the standard output of the script.
+    #[derivative(Default(value = "None"))]
+    pub stdout_regex: Option<String>,
+
+    /// Regular expression that must not match the standard output of the script.
+    #[derivative(Default(value = "None"))]
+    pub stdout_not_regex: Option<String>,
+
+    /// Regular expression that must match the standard error of the script.
+    #[derivative(Default(value = "None"))]
+    pub stderr_regex: Option<String>,
+
+    /// Regular expression that must not match the standard error of the script.
+    #[derivative(Default(value = "None"))]
+    pub stderr_not_regex: Option<String>,
+
+    /// Maximum number of rounds of the passes. The passes are executed until they reduce the
+    /// program if no limit is set.
+    #[derivative(Default(value = "None"))]
+    pub max_rounds: Option<usize>,
+
+    /// Number of the check scripts executed concurrently.
+    #[derivative(Default(value = "1"))]
+    pub jobs: usize,
+
+    /// Try to reduce the input to a single subset on each iteration of the delta debugging.
+    #[derivative(Default(value = "true"))]
+    pub subsets: bool,
+
+    /// Reuse outcomes of the candidates tested in the previous runs. The outcomes are always kept
+    /// in the output directory.
+    #[derivative(Default(value = "false"))]
+    pub persist_cache: bool,
+
+    /// Continue the reduction from the checkpoint kept in the output directory.
+    #[derivative(Default(value = "false"))]
+    pub resume: bool,
+
     /// Remove the existent temporary directory if exists.
     #[derivative(Default(value = "false"))]
     pub force: bool,
 
-    /// Use files in the directory that contains the target file.
+    /// Use files in the directory that contains the target file. Each candidate is a copy of
+    /// this directory, and the script gets a path to it.
     #[derivative(Default(value = "false"))]
     pub recursive: bool,
 
-    /// Passes to run.
-    #[derivative(Default(value = "defaults::PASSES_CONFIG"))]
-    pub passes: PassesConfig,
+    /// Level of the output on stderr: 0 hides the progress, 1 shows it, 2 and 3 enable the info
+    /// and debug log messages.
+    #[derivative(Default(value = "1"))]
+    pub verbosity: u8,
+
+    /// Passes to run in the given order.
+    #[derivative(Default(value = "parse_passes(None).unwrap()"))]
+    pub passes: Vec<PassConfig>,
+
+    /// Print the available passes and exit.
+    #[derivative(Default(value = "false"))]
+    pub list_passes: bool,
+
+    /// Print the effective configuration in the format of the configuration file and exit.
+    #[derivative(Default(value = "false"))]
+    pub dump_config: bool,
+
+    /// Replay the trace of the reduction at the given path instead of reducing the program.
+    #[derivative(Default(value = "None"))]
+    pub replay: Option<String>,
+}
+
+/// Returns path to the backup of the file created in the in-place mode.
+pub fn backup_path(path: &str) -> String {
+    format!("{}.{}", path, defaults::BACKUP_EXT)
 }
 
 /// Returns absolute path from the given `path`.
@@ -70,21 +234,71 @@ fn abs_path(path: &str) -> Result<String, String> {
     }
 }
 
-/// Returns passes configuraiton based on the given CLI argument content.
-pub fn parse_passes(arg: Option<&str>) -> Result<PassesConfig, String> {
-    if arg.is_none() {
-        return Err("No passes enabled".to_string());
+/// Parses the numeric value of the CLI argument.
+fn parse_number<T>(value: &str, what: &str) -> Result<T, String>
+where
+    T: FromStr,
+{
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid {}: {}", what, value))
+}
+
+/// Returns number of the signal given by its number or name, e.g. `11`, `SEGV` or `SIGSEGV`.
+pub fn parse_signal(arg: &str) -> Result<i32, String> {
+    if let Ok(signal) = arg.parse::<i32>() {
+        return Ok(signal);
     }
-    let mut passes = PassesConfig {
-        imports: false,
-        top: false,
-    };
-    for pass_name in arg.unwrap().split(';') {
-        match pass_name {
-            "imports" => passes.imports = true,
-            "top" => passes.top = true,
-            _ => return Err(format!("Unknown pass: {}", pass_name)),
+    match arg.trim_start_matches("SIG") {
+        "HUP" => Ok(1),
+        "INT" => Ok(2),
+        "QUIT" => Ok(3),
+        "ILL" => Ok(4),
+        "TRAP" => Ok(5),
+        "ABRT" => Ok(6),
+        "FPE" => Ok(8),
+        "KILL" => Ok(9),
+        "SEGV" => Ok(11),
+        "PIPE" => Ok(13),
+        "ALRM" => Ok(14),
+        "TERM" => Ok(15),
+        _ => Err(format!("Unknown signal: {}", arg)),
+    }
+}
+
+/// Returns the pipeline of the passes given in the CLI argument, e.g. `top,hdd:max-depth=3`. The
+/// passes may be separated with `,` or `;`, and the options of a pass are separated with `:`. The
+/// default pipeline is returned if no argument is given.
+pub fn parse_passes(arg: Option<&str>) -> Result<Vec<PassConfig>, String> {
+    let mut passes = vec![];
+    for item in arg.unwrap_or(defaults::PASSES).split([',', ';']) {
+        let mut parts = item.trim().split(':');
+        let name = parts.next().unwrap_or("");
+        if name.is_empty() {
+            continue;
+        }
+        let info = match find_pass(name) {
+            Some(info) => info,
+            None => return Err(format!("Unknown pass: {}", name)),
+        };
+        let mut options = vec![];
+        for option in parts {
+            let (option, value) = match option.split_once('=') {
+                Some(o) => o,
+                None => return Err(format!("Invalid option of the {} pass: {}", name, option)),
+            };
+            if !info.options.iter().any(|o| o.name == option) {
+                return Err(format!("Unknown option of the {} pass: {}", name, option));
+            }
+            options.push((option.to_string(), value.to_string()));
         }
+        passes.push(PassConfig {
+            name: name.to_string(),
+            options,
+        });
+    }
+    if passes.is_empty() {
+        return Err("No passes enabled".to_string());
     }
     Ok(passes)
 }
@@ -96,23 +310,42 @@ impl App {
             .author("Georgiy Komarov <jubnzv@gmail.com>")
             .arg(
                 clap::Arg::new(args::SCRIPT)
-                    .help("Script that checks failure")
-                    .required(true)
+                    .help("Script that checks failure or a command line where {} is replaced with the path to the candidate")
+                    .required(false)
                     .index(1),
             )
             .arg(
                 clap::Arg::new(args::FILE)
                     .help("Path to Lua file")
-                    .required(true)
+                    .required(false)
                     .index(2),
             )
+            .arg(
+                clap::Arg::new(args::CONFIG)
+                    .short('c')
+                    .long("config")
+                    .help("Path to the configuration file. dd.toml in the current directory is used by default")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::DUMP_CONFIG)
+                    .long("dump-config")
+                    .help("Print the effective configuration and exit")
+                    .takes_value(false),
+            )
             .arg(
                 clap::Arg::new(args::PASSES)
                     .short('p')
                     .long("passes")
-                    .help("Enabled passes")
+                    .help("Comma-separated list of the passes to run in the given order, e.g. top,hdd:max-depth=3")
                     .takes_value(true),
             )
+            .arg(
+                clap::Arg::new(args::LIST_PASSES)
+                    .long("list-passes")
+                    .help("Print the available passes and their options")
+                    .takes_value(false),
+            )
             .arg(
                 clap::Arg::new(args::OUTPUT)
                     .short('o')
@@ -120,6 +353,26 @@ impl App {
                     .help("Path to created temporary directory")
                     .takes_value(true),
             )
+            .arg(
+                clap::Arg::new(args::RESULT)
+                    .long("result")
+                    .help("Path to the file where the reduced program will be written")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::EVENTS)
+                    .long("events")
+                    .value_name("FILE")
+                    .help("Write the reduction events in the JSON Lines format to the file, or to stdout if it is '-'")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::IN_PLACE)
+                    .short('i')
+                    .long("in-place")
+                    .help("Replace the Lua file with the reduced program keeping a .orig backup")
+                    .takes_value(false),
+            )
             .arg(
                 clap::Arg::new(args::TIMEOUT)
                     .short('t')
@@ -127,6 +380,98 @@ impl App {
                     .help("Timeout to execute the script in seconds")
                     .takes_value(true),
             )
+            .arg(
+                clap::Arg::new(args::TIMEOUT_INTERESTING)
+                    .long("timeout-interesting")
+                    .help("Consider the candidates that exceed the timeout as reproducing the failure")
+                    .takes_value(false),
+            )
+            .arg(
+                clap::Arg::new(args::CHECK_EMPTY)
+                    .long("check-empty")
+                    .help("Check that the script doesn't reproduce the failure for an empty file")
+                    .takes_value(false),
+            )
+            .arg(
+                clap::Arg::new(args::RETRIES)
+                    .long("retries")
+                    .help("Number of times to run the script again when the outcome is unresolved (exit code 125)")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::CONFIRM)
+                    .long("confirm")
+                    .help("Number of times to run the script again to confirm that the candidate reproduces the failure")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::EXIT_CODE)
+                    .long("exit-code")
+                    .help("Exit code of the script that reproduces the failure")
+                    .takes_value(true)
+                    .multiple_occurrences(true),
+            )
+            .arg(
+                clap::Arg::new(args::SIGNAL)
+                    .long("signal")
+                    .help("Signal that kills the script when the failure is reproduced")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::STDOUT_REGEX)
+                    .long("stdout-regex")
+                    .help("Regular expression that must match the standard output of the script")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::STDOUT_NOT_REGEX)
+                    .long("stdout-not-regex")
+                    .help("Regular expression that must not match the standard output of the script")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::STDERR_REGEX)
+                    .long("stderr-regex")
+                    .help("Regular expression that must match the standard error of the script")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::STDERR_NOT_REGEX)
+                    .long("stderr-not-regex")
+                    .help("Regular expression that must not match the standard error of the script")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::MAX_ROUNDS)
+                    .long("max-rounds")
+                    .help("Maximum number of rounds of the passes")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::JOBS)
+                    .short('j')
+                    .long("jobs")
+                    .help("Number of the check scripts executed concurrently")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::new(args::NO_SUBSETS)
+                    .long("no-subsets")
+                    .help("Test only complements of the subsets during the delta debugging")
+                    .takes_value(false),
+            )
+            .arg(
+                clap::Arg::new(args::PERSIST_CACHE)
+                    .long("persist-cache")
+                    .help("Reuse outcomes of the candidates tested in the previous runs")
+                    .takes_value(false),
+            )
+            .arg(
+                clap::Arg::new(args::RESUME)
+                    .long("resume")
+                    .help("Continue the reduction interrupted in the output directory")
+                    .takes_value(false),
+            )
             .arg(
                 clap::Arg::new(args::FORCE)
                     .short('f')
@@ -138,34 +483,216 @@ impl App {
                 clap::Arg::new(args::RECURSIVE)
                     .short('r')
                     .long("recursive")
-                    .help("Use the whole directory that contains Lua file")
+                    .help("Use the whole directory that contains Lua file. The script gets the path to the directory.")
                     .takes_value(false),
             )
             .arg(
                 clap::Arg::new(args::VERBOSE)
                     .short('v')
-                    .multiple_values(true)
-                    .takes_value(true)
+                    .long("verbose")
+                    .multiple_occurrences(true)
+                    .takes_value(false)
                     .help("Sets the level of verbosity"),
             )
+            .arg(
+                clap::Arg::new(args::QUIET)
+                    .short('q')
+                    .long("quiet")
+                    .conflicts_with(args::VERBOSE)
+                    .help("Don't show the progress and the statistics")
+                    .takes_value(false),
+            )
+            .subcommand(
+                clap::App::new(args::REPLAY)
+                    .about("Applies the trace of the reduction to the original files checking each step with the script")
+                    .arg(
+                        clap::Arg::new(args::TRACE)
+                            .help("Path to the trace, e.g. /tmp/dd/trace.jsonl")
+                            .required(true)
+                            .index(1),
+                    ),
+            )
             .get_matches();
 
-        let script = match abs_path(matches.value_of(args::SCRIPT).unwrap()) {
-            Ok(path) => path,
-            Err(err) => return Err(err),
+        if let Some(replay) = matches.subcommand_matches(args::REPLAY) {
+            return Ok(App {
+                replay: replay.value_of(args::TRACE).map(|s| s.to_string()),
+                ..App::new()
+            });
+        }
+
+        if matches.is_present(args::LIST_PASSES) {
+            return Ok(App {
+                list_passes: true,
+                ..App::new()
+            });
+        }
+
+        let mut app = App::new();
+        let config_file = match matches.value_of(args::CONFIG) {
+            Some(path) => Some(path),
+            None if Path::new(defaults::CONFIG_FILE).is_file() => Some(defaults::CONFIG_FILE),
+            None => None,
+        };
+        if let Some(path) = config_file {
+            Config::load(path)?.apply(&mut app)?;
+        }
+
+        // The options given in the command line override the configuration file.
+        let value = |arg: &str| matches.value_of(arg).map(|s| s.to_string());
+        if let Some(script) = value(args::SCRIPT) {
+            app.script = script;
+        }
+        if let Some(file) = value(args::FILE) {
+            app.file = file;
+        }
+        if let Some(output) = value(args::OUTPUT) {
+            app.output_dir = output;
+        }
+        if let Some(result) = value(args::RESULT) {
+            app.result = Some(result);
+        }
+        if let Some(events) = value(args::EVENTS) {
+            app.events = Some(events);
+        }
+        if let Some(timeout) = matches.value_of(args::TIMEOUT) {
+            app.timeout = Some(parse_number(timeout, "timeout")?);
+        }
+        if let Some(retries) = matches.value_of(args::RETRIES) {
+            app.retries = parse_number(retries, "number of retries")?;
+        }
+        if let Some(confirm) = matches.value_of(args::CONFIRM) {
+            app.confirm = parse_number(confirm, "number of confirmations")?;
+        }
+        if let Some(codes) = matches.values_of(args::EXIT_CODE) {
+            app.exit_codes = codes
+                .map(|c| parse_number(c, "exit code"))
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+        if let Some(signal) = matches.value_of(args::SIGNAL) {
+            app.signal = Some(parse_signal(signal)?);
+        }
+        let regexes = [
+            (args::STDOUT_REGEX, &mut app.stdout_regex),
+            (args::STDOUT_NOT_REGEX, &mut app.stdout_not_regex),
+            (args::STDERR_REGEX, &mut app.stderr_regex),
+            (args::STDERR_NOT_REGEX, &mut app.stderr_not_regex),
+        ];
+        for (arg, field) in regexes {
+            if let Some(regex) = value(arg) {
+                *field = Some(regex);
+            }
+        }
+        if let Some(rounds) = matches.value_of(args::MAX_ROUNDS) {
+            app.max_rounds = Some(parse_number(rounds, "number of rounds")?);
+        }
+        if let Some(jobs) = matches.value_of(args::JOBS) {
+            app.jobs = match jobs.parse::<usize>() {
+                Ok(jobs) if jobs > 0 => jobs,
+                _ => return Err(format!("Invalid number of jobs: {}", jobs)),
+            };
+        }
+        app.in_place |= matches.is_present(args::IN_PLACE);
+        app.timeout_interesting |= matches.is_present(args::TIMEOUT_INTERESTING);
+        app.check_empty |= matches.is_present(args::CHECK_EMPTY);
+        if matches.is_present(args::QUIET) {
+            app.verbosity = 0;
+        } else if matches.is_present(args::VERBOSE) {
+            let level = 1 + matches.occurrences_of(args::VERBOSE);
+            app.verbosity = std::cmp::min(level, u8::MAX as u64) as u8;
+        }
+        app.subsets &= !matches.is_present(args::NO_SUBSETS);
+        app.persist_cache |= matches.is_present(args::PERSIST_CACHE);
+        app.resume |= matches.is_present(args::RESUME);
+        app.force |= matches.is_present(args::FORCE);
+        app.recursive |= matches.is_present(args::RECURSIVE);
+        if let Some(passes) = matches.value_of(args::PASSES) {
+            app.passes = parse_passes(Some(passes))?;
+        }
+
+        app.dump_config = matches.is_present(args::DUMP_CONFIG);
+        if app.dump_config {
+            return Ok(app);
+        }
+        if app.script.is_empty() || app.file.is_empty() {
+            return Err("The script and the Lua file must be given".to_string());
+        }
+        // A command line is kept as is, so its program is looked up in PATH.
+        if Path::new(&app.script).is_file() {
+            app.script = abs_path(&app.script)?;
+        }
+        Ok(app)
+    }
+
+    /// Returns path to the file that contains the smallest failing program found so far. In the
+    /// recursive mode it is a directory that contains the whole project.
+    pub fn best_file(&self) -> String {
+        let name = if self.recursive {
+            defaults::BEST_DIR
+        } else {
+            defaults::BEST_FILE
         };
+        String::from(
+            std::path::PathBuf::from(&self.output_dir)
+                .join(name)
+                .to_string_lossy(),
+        )
+    }
+
+    /// Returns path to the file where the outcomes of the tested candidates are persisted.
+    pub fn cache_file(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.output_dir)
+                .join(defaults::CACHE_FILE)
+                .to_string_lossy(),
+        )
+    }
+
+    /// Returns path to the file where the accepted transformations are recorded.
+    pub fn trace_file(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.output_dir)
+                .join(defaults::TRACE_FILE)
+                .to_string_lossy(),
+        )
+    }
+
+    /// Returns path to the file that keeps the progress of the reduction.
+    pub fn checkpoint_file(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.output_dir)
+                .join(defaults::CHECKPOINT_FILE)
+                .to_string_lossy(),
+        )
+    }
+
+    /// Returns path to the file that contains the summary of the reduction.
+    pub fn summary_file(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.output_dir)
+                .join(defaults::SUMMARY_FILE)
+                .to_string_lossy(),
+        )
+    }
+
+    /// Returns path to the input with the given `name` checked before the reduction. In the
+    /// recursive mode it is a directory that contains the whole project.
+    pub fn sanity_file(&self, name: &str) -> String {
+        let name = if self.recursive {
+            name.to_string()
+        } else {
+            format!("{}.lua", name)
+        };
+        String::from(
+            std::path::PathBuf::from(&self.output_dir)
+                .join(defaults::SANITY_DIR)
+                .join(name)
+                .to_string_lossy(),
+        )
+    }
 
-        Ok(App {
-            file: matches.value_of(args::FILE).unwrap().to_string(),
-            script,
-            output_dir: matches
-                .value_of(args::OUTPUT)
-                .unwrap_or(defaults::OUTPUT_DIR)
-                .to_string(),
-            timeout: matches.value_of_t(args::TIMEOUT).ok(),
-            force: matches.is_present(args::FORCE),
-            recursive: matches.is_present(args::RECURSIVE),
-            passes: parse_passes(matches.value_of(args::PASSES))?,
-        })
+    /// Returns path to the backup of the tested file created in the in-place mode.
+    pub fn backup_file(&self) -> String {
+        backup_path(&self.file)
     }
 }
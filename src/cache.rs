@@ -0,0 +1,160 @@
+//! Cache memoizes outcomes of the check script for the candidates that were already tested.
+//!
+//! The candidates are identified by a hash of their content and the configuration of the check
+//! script. The cache may be persisted in the output directory, so the next run of the same
+//! reduction doesn't execute the script for the known candidates.
+use crate::error::Error;
+use crate::passes::TestOutcome;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Implementation of the 64-bit FNV-1a hash function. It is used instead of the hasher from the
+/// standard library, because the persisted keys must be the same in different builds of `dd`.
+pub struct Hasher(u64);
+
+impl Hasher {
+    pub fn new() -> Self {
+        Hasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for b in bytes.iter() {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Writes a string followed by a separator, so the sequences of strings are distinguishable.
+    pub fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the hash of the string.
+    pub fn hash_str(s: &str) -> u64 {
+        let mut hasher = Hasher::new();
+        hasher.write_str(s);
+        hasher.finish()
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+pub struct Cache {
+    entries: HashMap<u64, TestOutcome>,
+    /// File where the entries are persisted.
+    file: Option<PathBuf>,
+    hits: usize,
+    misses: usize,
+}
+
+impl Cache {
+    /// Creates the cache that writes the entries to `file`. The existing entries are ignored.
+    pub fn with_file(file: PathBuf) -> Self {
+        Cache {
+            file: Some(file),
+            ..Cache::default()
+        }
+    }
+
+    /// Creates the cache persisted in `file`. The existing entries are loaded from it.
+    pub fn persistent(file: PathBuf) -> Self {
+        let mut entries = HashMap::new();
+        if let Ok(content) = fs::read_to_string(&file) {
+            for line in content.lines() {
+                let mut parts = line.split_whitespace();
+                let key = parts.next().and_then(|k| u64::from_str_radix(k, 16).ok());
+                let outcome = match parts.next() {
+                    Some("Pass") => Some(TestOutcome::Pass),
+                    Some("Fail") => Some(TestOutcome::Fail),
+                    Some("Timeout") => Some(TestOutcome::Timeout),
+                    _ => None,
+                };
+                if let (Some(key), Some(outcome)) = (key, outcome) {
+                    entries.insert(key, outcome);
+                }
+            }
+        }
+        log::debug!(
+            "Loaded {} cache entries from {}",
+            entries.len(),
+            file.display()
+        );
+        Cache {
+            entries,
+            file: Some(file),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the outcome of the candidate with the given `key` if it is known.
+    pub fn get(&mut self, key: u64) -> Option<TestOutcome> {
+        match self.entries.get(&key) {
+            Some(outcome) => {
+                self.hits += 1;
+                Some(outcome.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Saves the outcome of the candidate. The unresolved outcomes are not cached, because they
+    /// are caused by the errors that may disappear on the next execution.
+    pub fn insert(&mut self, key: u64, outcome: &TestOutcome) -> Result<(), Error> {
+        if let TestOutcome::Unresolved(_) = outcome {
+            return Ok(());
+        }
+        self.entries.insert(key, outcome.clone());
+        if let Some(file) = &self.file {
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file)
+                .and_then(|mut f| writeln!(f, "{:016x} {}", key, outcome))
+                .map_err(|err| Error::new(format!("{}: {}", file.display(), err)))?;
+        }
+        Ok(())
+    }
+
+    /// Writes all the entries to the file, replacing its content.
+    pub fn save(&self) -> Result<(), Error> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let content = self
+            .entries
+            .iter()
+            .map(|(key, outcome)| format!("{:016x} {}\n", key, outcome))
+            .collect::<String>();
+        fs::write(file, content).map_err(|err| Error::new(format!("{}: {}", file.display(), err)))
+    }
+
+    /// Returns the known outcome of the candidate without counting it as a hit.
+    pub fn peek(&self, key: u64) -> Option<&TestOutcome> {
+        self.entries.get(&key)
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
@@ -0,0 +1,80 @@
+//! Checkpoint keeps the progress of the reduction in the output directory, so an interrupted
+//! reduction may be continued with `--resume` instead of starting from the original file.
+//!
+//! The checkpoint is rewritten before each pass, before each batch of the delta debugging and
+//! when a smaller failing program is found. The outcomes of the tested candidates are restored
+//! from the persistent cache.
+use crate::error::Error;
+use crate::progress::Size;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Progress of the delta debugging in the pass executed at the moment.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DdminState {
+    /// Name of the pass that reduces the sequence.
+    pub pass: String,
+    /// File reduced by the pass.
+    pub target: String,
+    /// Hash that identifies the reduced sequence, e.g. one of the blocks reduced by the pass.
+    pub sequence: u64,
+    /// Length of the reduced sequence.
+    pub length: usize,
+    pub granularity: usize,
+    /// Index of the next tested step with this granularity.
+    pub position: usize,
+}
+
+impl DdminState {
+    /// Returns true if the `other` state belongs to the delta debugging of the same sequence.
+    pub fn same_sequence(&self, other: &DdminState) -> bool {
+        self.pass == other.pass
+            && self.target == other.target
+            && self.sequence == other.sequence
+            && self.length == other.length
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Number of the completed rounds of the passes.
+    pub round: usize,
+    /// True if the failure has been reproduced.
+    pub reproduced: bool,
+    /// Configuration of the pass executed at the moment, e.g. `hdd:max-depth=3`.
+    pub pass: Option<String>,
+    /// File reduced by the pass.
+    pub target: Option<String>,
+    /// Size of the original program.
+    #[serde(default)]
+    pub initial: Option<Size>,
+    pub ddmin: Option<DdminState>,
+    /// Source code of the Lua files in the smallest failing program found so far.
+    pub files: BTreeMap<String, String>,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint from `path`. Returns `None` if it doesn't exist.
+    pub fn load(path: &str) -> Result<Option<Self>, Error> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::new(format!("{}: {}", path, err))),
+        };
+        match toml::from_str(&content) {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(err) => Err(Error::new(format!("{}: {}", path, err))),
+        }
+    }
+
+    /// Writes the checkpoint to `path`. The previous checkpoint is replaced only when the new one
+    /// is completely written.
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let content = toml::to_string(self).map_err(|err| Error::new(err.to_string()))?;
+        let temp = format!("{}.tmp", path);
+        fs::write(&temp, content)
+            .and_then(|_| fs::rename(&temp, path))
+            .map_err(|err| Error::new(format!("Cannot write to '{}': {}", path, err)))
+    }
+}
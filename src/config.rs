@@ -0,0 +1,194 @@
+//! Config maps the configuration file (`dd.toml` by default) onto the application configuration,
+//! so a reduction may be repeated without reconstructing its command line. The keys of the file
+//! are named after the long command line options:
+//!
+//! ```toml
+//! script = "lua {}"
+//! file = "bug.lua"
+//! timeout = 10
+//! stderr-regex = "attempt to index"
+//! passes = ["top", "hdd:max-depth=3"]
+//! ```
+//!
+//! Relative paths in the file are resolved against its directory. The options given in the
+//! command line override the values from the file.
+use crate::app::{parse_passes, App};
+use crate::events;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub script: Option<String>,
+    pub file: Option<String>,
+    pub output: Option<String>,
+    pub result: Option<String>,
+    pub in_place: Option<bool>,
+    /// Path to the file with the reduction events, or `-` for the standard output.
+    pub events: Option<String>,
+    pub timeout: Option<u32>,
+    pub timeout_interesting: Option<bool>,
+    pub check_empty: Option<bool>,
+    pub retries: Option<usize>,
+    pub confirm: Option<usize>,
+    pub exit_codes: Option<Vec<i32>>,
+    pub signal: Option<i32>,
+    pub stdout_regex: Option<String>,
+    pub stdout_not_regex: Option<String>,
+    pub stderr_regex: Option<String>,
+    pub stderr_not_regex: Option<String>,
+    pub max_rounds: Option<usize>,
+    pub jobs: Option<usize>,
+    pub subsets: Option<bool>,
+    pub persist_cache: Option<bool>,
+    pub force: Option<bool>,
+    pub recursive: Option<bool>,
+    pub verbosity: Option<u8>,
+    /// Passes in the same format as in the command line, e.g. `hdd:max-depth=3`.
+    pub passes: Option<Vec<String>>,
+}
+
+/// Makes the relative `path` relative to the `dir`.
+fn resolve(dir: &Path, path: &mut Option<String>) {
+    if let Some(p) = path {
+        if Path::new(p).is_relative() {
+            *p = dir.join(&p).display().to_string();
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let mut config: Config =
+            toml::from_str(&content).map_err(|err| format!("{}: {}", path, err))?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        resolve(dir, &mut config.file);
+        resolve(dir, &mut config.output);
+        resolve(dir, &mut config.result);
+        if config.events.as_deref() != Some(events::STDOUT) {
+            resolve(dir, &mut config.events);
+        }
+        // The script may be a command line, which is kept as is.
+        if let Some(script) = &config.script {
+            if dir.join(script).is_file() {
+                resolve(dir, &mut config.script);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns the configuration that contains all the settings of the `app`.
+    pub fn from_app(app: &App) -> Self {
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        Config {
+            script: non_empty(&app.script),
+            file: non_empty(&app.file),
+            output: Some(app.output_dir.clone()),
+            result: app.result.clone(),
+            in_place: Some(app.in_place),
+            events: app.events.clone(),
+            timeout: app.timeout,
+            timeout_interesting: Some(app.timeout_interesting),
+            check_empty: Some(app.check_empty),
+            retries: Some(app.retries),
+            confirm: Some(app.confirm),
+            exit_codes: (!app.exit_codes.is_empty()).then(|| app.exit_codes.clone()),
+            signal: app.signal,
+            stdout_regex: app.stdout_regex.clone(),
+            stdout_not_regex: app.stdout_not_regex.clone(),
+            stderr_regex: app.stderr_regex.clone(),
+            stderr_not_regex: app.stderr_not_regex.clone(),
+            max_rounds: app.max_rounds,
+            jobs: Some(app.jobs),
+            subsets: Some(app.subsets),
+            persist_cache: Some(app.persist_cache),
+            force: Some(app.force),
+            recursive: Some(app.recursive),
+            verbosity: Some(app.verbosity),
+            passes: Some(app.passes.iter().map(|p| p.to_string()).collect()),
+        }
+    }
+
+    /// Sets the values given in this configuration in the `app`.
+    pub fn apply(self, app: &mut App) -> Result<(), String> {
+        if let Some(script) = self.script {
+            app.script = script;
+        }
+        if let Some(file) = self.file {
+            app.file = file;
+        }
+        if let Some(output) = self.output {
+            app.output_dir = output;
+        }
+        if self.result.is_some() {
+            app.result = self.result;
+        }
+        if self.events.is_some() {
+            app.events = self.events;
+        }
+        if self.timeout.is_some() {
+            app.timeout = self.timeout;
+        }
+        if let Some(retries) = self.retries {
+            app.retries = retries;
+        }
+        if let Some(confirm) = self.confirm {
+            app.confirm = confirm;
+        }
+        if let Some(codes) = self.exit_codes {
+            app.exit_codes = codes;
+        }
+        if self.signal.is_some() {
+            app.signal = self.signal;
+        }
+        let regexes = [
+            (self.stdout_regex, &mut app.stdout_regex),
+            (self.stdout_not_regex, &mut app.stdout_not_regex),
+            (self.stderr_regex, &mut app.stderr_regex),
+            (self.stderr_not_regex, &mut app.stderr_not_regex),
+        ];
+        for (value, field) in regexes {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        if self.max_rounds.is_some() {
+            app.max_rounds = self.max_rounds;
+        }
+        match self.jobs {
+            Some(0) => return Err("Invalid number of jobs: 0".to_string()),
+            Some(jobs) => app.jobs = jobs,
+            None => (),
+        }
+        let flags = [
+            (self.in_place, &mut app.in_place),
+            (self.timeout_interesting, &mut app.timeout_interesting),
+            (self.check_empty, &mut app.check_empty),
+            (self.subsets, &mut app.subsets),
+            (self.persist_cache, &mut app.persist_cache),
+            (self.force, &mut app.force),
+            (self.recursive, &mut app.recursive),
+        ];
+        for (value, field) in flags {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        if let Some(verbosity) = self.verbosity {
+            app.verbosity = verbosity;
+        }
+        if let Some(passes) = self.passes {
+            app.passes = parse_passes(Some(&passes.join(",")))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the configuration in the format of the configuration file.
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,457 @@
+//! Context keeps the state shared between the driver and the passes during the reduction.
+use crate::app::App;
+use crate::cache::{Cache, Hasher};
+use crate::checkpoint::{Checkpoint, DdminState};
+use crate::error::Error;
+use crate::events::{outcome_name, Event, Events};
+use crate::oracle::{Execution, Flaky, Oracle, ScriptOracle};
+use crate::passes::{Candidate, TestOutcome};
+use crate::progress::{Progress, Size, Snapshot};
+use crate::reducer::Accepted;
+use crate::trace::Trace;
+use crate::workspace::Workspace;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// Oracle that checks the candidates during the reduction.
+enum Checker<'app> {
+    /// The check command that gets the candidates written to disk.
+    Script(ScriptOracle),
+    /// The oracle given to the library. It gets the source code of the candidates, so nothing is
+    /// written to the output directory.
+    InProcess(Box<dyn Oracle + 'app>),
+}
+
+/// Callback that receives the updates of the progress.
+pub type ProgressListener<'app> = Box<dyn Fn(&Snapshot) + 'app>;
+
+/// Callback that receives the accepted candidates.
+pub type AcceptedListener<'app> = Box<dyn Fn(&Accepted) + 'app>;
+
+pub struct Context<'app> {
+    app: &'app App,
+    workspace: RefCell<Workspace>,
+    cache: RefCell<Cache>,
+    oracle: Checker<'app>,
+    checkpoint: RefCell<Checkpoint>,
+    /// State of the delta debugging restored from the checkpoint. It is used by the first
+    /// delta debugging in the resumed pass.
+    resumed_ddmin: RefCell<Option<DdminState>>,
+    progress: RefCell<Progress>,
+    events: RefCell<Events>,
+    /// Trace of the accepted candidates. It is not written by the in-process oracle.
+    trace: RefCell<Option<Trace>>,
+    // The listeners are not kept in `RefCell`, so the context remains covariant over `'app`.
+    on_progress: Option<ProgressListener<'app>>,
+    on_accepted: Option<AcceptedListener<'app>>,
+    /// Identifiers of the tested candidates by their keys in the cache.
+    ids: RefCell<HashMap<u64, usize>>,
+}
+
+/// Removes the file or the directory at `path` if it exists.
+fn remove_path(path: &str) -> Result<(), Error> {
+    let p = Path::new(path);
+    let result = if p.is_dir() {
+        fs::remove_dir_all(p)
+    } else if p.exists() {
+        fs::remove_file(p)
+    } else {
+        Ok(())
+    };
+    result.map_err(|err| Error::new(format!("Cannot remove '{}': {}", path, err)))
+}
+
+impl<'app> Context<'app> {
+    pub fn new(app: &'app App) -> Result<Self, Error> {
+        let cache = if app.persist_cache || app.resume {
+            Cache::persistent(app.cache_file().into())
+        } else {
+            Cache::with_file(app.cache_file().into())
+        };
+        let mut workspace = Workspace::from_app(app)?;
+        let mut checkpoint = if app.resume {
+            match Checkpoint::load(&app.checkpoint_file())? {
+                Some(checkpoint) => checkpoint,
+                None => {
+                    return Err(Error::new(format!(
+                        "Cannot resume: no checkpoint in '{}'",
+                        app.output_dir
+                    )))
+                }
+            }
+        } else {
+            Checkpoint::default()
+        };
+        let trace = Trace::new(app, &workspace.files_with(&workspace.target_source(), &[]))?;
+        if app.resume {
+            workspace
+                .restore(checkpoint.files.clone())
+                .map_err(|err| Error::new(format!("Cannot resume: {}", err)))?;
+            checkpoint.files = workspace.files_with(&workspace.target_source(), &[]);
+            checkpoint.target = checkpoint.target.map(|t| workspace.key(&t));
+            if let Some(state) = checkpoint.ddmin.as_mut() {
+                state.target = workspace.key(&state.target);
+            }
+        }
+        Ok(Context {
+            app,
+            workspace: RefCell::new(workspace),
+            cache: RefCell::new(cache),
+            oracle: Checker::Script(ScriptOracle::from_app(app)?),
+            checkpoint: RefCell::new(checkpoint),
+            resumed_ddmin: RefCell::new(None),
+            progress: RefCell::new(Progress::new(app.verbosity)),
+            events: RefCell::new(Events::new(app.events.clone(), app.resume)),
+            trace: RefCell::new(Some(trace)),
+            on_progress: None,
+            on_accepted: None,
+            ids: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Creates the context of the reduction of the `source` checked by the in-process `oracle`.
+    /// The listeners receive the updates of the progress and the accepted candidates.
+    pub fn in_process(
+        app: &'app App,
+        source: &str,
+        oracle: Box<dyn Oracle + 'app>,
+        on_progress: Option<ProgressListener<'app>>,
+        on_accepted: Option<AcceptedListener<'app>>,
+    ) -> Self {
+        Context {
+            app,
+            workspace: RefCell::new(Workspace::from_source(&app.file, source)),
+            cache: RefCell::new(Cache::default()),
+            oracle: Checker::InProcess(oracle),
+            checkpoint: RefCell::new(Checkpoint::default()),
+            resumed_ddmin: RefCell::new(None),
+            progress: RefCell::new(Progress::new(app.verbosity)),
+            events: RefCell::new(Events::new(app.events.clone(), false)),
+            trace: RefCell::new(None),
+            on_progress,
+            on_accepted,
+            ids: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns application configuration.
+    pub fn app(&self) -> &App {
+        self.app
+    }
+
+    pub fn workspace(&self) -> Ref<'_, Workspace> {
+        self.workspace.borrow()
+    }
+
+    pub fn workspace_mut(&self) -> RefMut<'_, Workspace> {
+        self.workspace.borrow_mut()
+    }
+
+    /// Returns the cache of the outcomes shared between the passes.
+    pub fn cache(&self) -> RefMut<'_, Cache> {
+        self.cache.borrow_mut()
+    }
+
+    /// Returns true if the candidates are written to disk and the state of the reduction is kept
+    /// in the output directory.
+    pub fn writes_output(&self) -> bool {
+        matches!(self.oracle, Checker::Script(_))
+    }
+
+    /// Returns true if the `outcome` reproduces the failure.
+    pub fn is_interesting(&self, outcome: &TestOutcome) -> bool {
+        match &self.oracle {
+            Checker::Script(oracle) => oracle.is_interesting(outcome),
+            Checker::InProcess(_) => match outcome {
+                TestOutcome::Fail => true,
+                TestOutcome::Timeout => self.app.timeout_interesting,
+                _ => false,
+            },
+        }
+    }
+
+    /// Returns the candidates for which the script returned different outcomes.
+    pub fn flaky(&self) -> Vec<Flaky> {
+        match &self.oracle {
+            Checker::Script(oracle) => oracle.flaky(),
+            Checker::InProcess(_) => vec![],
+        }
+    }
+
+    /// Checks the `source` of the reduced file before the reduction. The script gets it in the
+    /// sanity file with the given `name`. Returns the outcome and the report of the check.
+    pub fn probe(&self, name: &str, source: &str) -> Result<(TestOutcome, String), Error> {
+        match &self.oracle {
+            Checker::Script(oracle) => {
+                let target = self
+                    .workspace()
+                    .write(&self.app.sanity_file(name), source, &[])?;
+                let probe = oracle.probe(&target)?;
+                Ok((probe.outcome.clone(), probe.to_string()))
+            }
+            Checker::InProcess(oracle) => {
+                let outcome = oracle.test(source);
+                let report = format!("Outcome: {}", outcome);
+                Ok((outcome, report))
+            }
+        }
+    }
+
+    /// Checks the `candidates` concurrently as described in `ScriptOracle::run_all`. Each
+    /// candidate is given with the path where it is written for the script, which is `None` for
+    /// the in-process oracle. The in-process oracle checks the candidates one by one until some
+    /// of them reproduces the failure.
+    pub fn run_all(&self, candidates: &[(Option<&str>, &Candidate)]) -> Vec<Option<Execution>> {
+        let oracle = match &self.oracle {
+            Checker::Script(oracle) => {
+                let targets = candidates
+                    .iter()
+                    .map(|(target, _)| target.unwrap_or_default())
+                    .collect::<Vec<_>>();
+                return oracle.run_all(&targets);
+            }
+            Checker::InProcess(oracle) => oracle,
+        };
+        let mut results = vec![];
+        let mut found = false;
+        for (_, candidate) in candidates.iter() {
+            if found {
+                results.push(None);
+                continue;
+            }
+            let started = Instant::now();
+            let outcome = oracle.test(&candidate.source);
+            found = self.is_interesting(&outcome);
+            results.push(Some(Execution {
+                outcome,
+                duration: started.elapsed(),
+            }));
+        }
+        results
+    }
+
+    /// Returns the progress shown on stderr with the cache counters updated.
+    pub fn progress(&self) -> RefMut<'_, Progress> {
+        let mut progress = self.progress.borrow_mut();
+        let cache = self.cache();
+        progress.set_cache(cache.hits(), cache.misses());
+        progress
+    }
+
+    /// Gives the state of the progress to the listener.
+    fn notify_progress(&self) {
+        if let Some(listener) = &self.on_progress {
+            let snapshot = self.progress().snapshot();
+            listener(&snapshot);
+        }
+    }
+
+    /// Returns the stream of the reduction events.
+    pub fn events(&self) -> RefMut<'_, Events> {
+        self.events.borrow_mut()
+    }
+
+    /// Opens the trace of the accepted transformations in the output directory.
+    pub fn open_trace(&self) -> Result<(), Error> {
+        match self.trace.borrow_mut().as_mut() {
+            Some(trace) => trace.open(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the progress of the reduction.
+    pub fn checkpoint(&self) -> RefMut<'_, Checkpoint> {
+        self.checkpoint.borrow_mut()
+    }
+
+    /// Writes the progress of the reduction to the output directory.
+    pub fn save_checkpoint(&self) -> Result<(), Error> {
+        if !self.writes_output() {
+            return Ok(());
+        }
+        self.checkpoint().save(&self.app.checkpoint_file())
+    }
+
+    /// Writes the progress of the reduction before running the `pass` for the reduced file.
+    pub fn start_pass(&self, pass: &str) -> Result<(), Error> {
+        {
+            let workspace = self.workspace();
+            let mut checkpoint = self.checkpoint();
+            checkpoint.pass = Some(pass.to_string());
+            checkpoint.target = Some(workspace.target().to_string());
+            checkpoint.ddmin = None;
+            checkpoint.files = workspace.files_with(&workspace.target_source(), &[]);
+        }
+        let size = self.workspace().measure();
+        self.progress()
+            .start_pass(pass, self.workspace().target(), size);
+        self.notify_progress();
+        if let Some(trace) = self.trace.borrow_mut().as_mut() {
+            trace.start_pass(&self.workspace().target_source());
+        }
+        self.events().emit(Event::PassStarted {
+            round: self.checkpoint().round + 1,
+            pass,
+            target: self.workspace().target(),
+            size: size.bytes,
+        })?;
+        self.save_checkpoint()
+    }
+
+    /// Collects the statistics of the pass finished for the reduced file. The pass has `reduced`
+    /// the program if it found a smaller failing candidate.
+    pub fn finish_pass(&self, reduced: bool) -> Result<(), Error> {
+        let size = self.workspace().measure();
+        self.progress().finish_pass(size);
+        self.notify_progress();
+        let pass = self.checkpoint().pass.clone().unwrap_or_default();
+        self.events().emit(Event::PassFinished {
+            pass: &pass,
+            target: self.workspace().target(),
+            reduced,
+            size: size.bytes,
+        })
+    }
+
+    /// Reports the `execution` of the oracle for the `candidate` with the given `id` and `key` in
+    /// the cache. The `target` is the path given to the script, and it is `None` for the
+    /// in-process oracle and the `cached` outcomes. The execution is `None` if the script was
+    /// killed, because another candidate reproduced the failure.
+    pub fn candidate_tested(
+        &self,
+        id: usize,
+        key: u64,
+        target: Option<&str>,
+        cached: bool,
+        candidate: &Candidate,
+        execution: Option<&Execution>,
+    ) -> Result<(), Error> {
+        self.ids.borrow_mut().insert(key, id);
+        let mut events = self.events();
+        if !events.enabled() {
+            return Ok(());
+        }
+        let pass = self.checkpoint().pass.clone().unwrap_or_default();
+        let (outcome, reason) = outcome_name(execution.map(|e| &e.outcome));
+        events.emit(Event::CandidateTested {
+            id,
+            pass: &pass,
+            file: target,
+            size: candidate.source.len(),
+            outcome,
+            reason,
+            cached,
+            duration: execution.map_or(0.0, |e| e.duration.as_secs_f64()),
+        })
+    }
+
+    /// Reports that the `candidate` is the smallest failing program found so far and records it
+    /// in the trace.
+    pub fn candidate_accepted(&self, candidate: &Candidate) -> Result<(), Error> {
+        let key = self.candidate_key(&candidate.source, &candidate.removed_files);
+        let id = self.ids.borrow().get(&key).copied();
+        let outcome = self.cache().peek(key).cloned();
+        let pass = self.checkpoint().pass.clone().unwrap_or_default();
+        if let Some(trace) = self.trace.borrow_mut().as_mut() {
+            trace.accept(
+                &pass,
+                self.workspace().target(),
+                id,
+                candidate,
+                outcome.as_ref(),
+            )?;
+        }
+        if let Some(listener) = &self.on_accepted {
+            listener(&Accepted {
+                pass: &pass,
+                source: &candidate.source,
+            });
+        }
+        match id {
+            Some(id) => self.events().emit(Event::CandidateAccepted {
+                id,
+                pass: &pass,
+                size: candidate.source.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the state of the delta debugging executed by the current pass. The delta debugging
+    /// tries `steps` candidates with the current granularity.
+    pub fn save_ddmin_state(&self, state: DdminState, steps: usize) -> Result<(), Error> {
+        self.progress()
+            .ddmin(state.granularity, state.position, steps);
+        self.notify_progress();
+        self.checkpoint().ddmin = Some(state);
+        self.save_checkpoint()
+    }
+
+    /// Sets the state of the delta debugging restored from the checkpoint for the next pass.
+    pub fn resume_ddmin(&self, state: Option<DdminState>) {
+        *self.resumed_ddmin.borrow_mut() = state;
+    }
+
+    /// Returns the state of the delta debugging restored from the checkpoint if it reduces the
+    /// same sequence as the `initial` state. The state is used only once.
+    pub fn take_resumed_ddmin(&self, initial: &DdminState) -> Option<DdminState> {
+        self.resumed_ddmin
+            .borrow_mut()
+            .take()
+            .filter(|state| state.same_sequence(initial))
+    }
+
+    /// Returns the key that identifies the candidate in the cache. The candidate contains `source`
+    /// in the reduced file and doesn't contain the `removed` files.
+    pub fn candidate_key(&self, source: &str, removed: &[String]) -> u64 {
+        let mut hasher = Hasher::new();
+        if let Checker::Script(oracle) = &self.oracle {
+            oracle.hash_into(&mut hasher);
+        }
+        self.workspace().hash(&mut hasher, source, removed);
+        hasher.finish()
+    }
+
+    /// Saves the smallest failing candidate found so far to the output directory. The candidate
+    /// contains `source` in the reduced file and doesn't contain the `removed` files. The previous
+    /// result is replaced only when the new one is completely written.
+    pub fn save_best(&self, source: &str, removed: &[String]) -> Result<(), Error> {
+        if self.writes_output() {
+            let best = self.app.best_file();
+            let temp = format!("{}.tmp", best);
+            remove_path(&temp)?;
+            self.workspace().write(&temp, source, removed)?;
+            if self.workspace().is_project() {
+                remove_path(&best)?;
+            }
+            fs::rename(&temp, &best)
+                .map_err(|err| Error::new(format!("Cannot write to '{}': {}", best, err)))?;
+        }
+        let files = self.workspace().files_with(source, removed);
+        self.progress()
+            .reduced(files.values().map(|s| Size::of_text(s)).sum());
+        self.notify_progress();
+        {
+            let mut checkpoint = self.checkpoint();
+            checkpoint.files = files;
+            checkpoint.reproduced = true;
+        }
+        self.save_checkpoint()
+    }
+
+    /// Writes the reduced program to the locations requested in the application configuration.
+    pub fn save_result(&self) -> Result<(), Error> {
+        let workspace = self.workspace();
+        if let Some(result) = &self.app.result {
+            remove_path(result)?;
+            workspace.save(result)?;
+        }
+        if self.app.in_place {
+            workspace.save_in_place()?;
+        }
+        Ok(())
+    }
+}
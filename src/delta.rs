@@ -1,75 +1,206 @@
 //! The delta module contains implementation of the common delta debugging algorithms.
 
+use crate::cache::Hasher;
+use crate::checkpoint::DdminState;
 use crate::error::Error;
-use crate::passes::{Pass, TestOutcome};
+use crate::passes::{Candidate, Pass};
+use crate::treesitter::Edit;
+use std::fmt;
 use tree_sitter::Node as TSNode;
 
-/// Remove all nodes occurred in the `complement`, because they doesn't cause a fail.
-fn remove_complement<'a>(seq: &mut Vec<TSNode<'a>>, complement: &[TSNode<'a>]) {
-    let new_seq: Vec<TSNode> = seq.iter().fold(vec![], |mut acc, node| {
-        if !complement.contains(node) {
-            acc.push(*node);
-        }
-        acc
-    });
-    let new_seq: &[TSNode<'a>] = &new_seq;
-    seq.drain(new_seq.len()..);
-    seq.clone_from_slice(new_seq);
+/// Remove all elements occurred in the `complement`, because they doesn't cause a fail.
+fn remove_complement<T: Clone + PartialEq>(seq: &mut Vec<T>, complement: &[T]) {
+    seq.retain(|e| !complement.contains(e));
 }
 
-/// Reduces the current sequence, using the outcome of the `test` function.
-/// It returns the nodes that causes the failure and the source code of the minimal reproducible
-/// example.
-///
-/// This function implements the Minimizing Delta Debugging algorithm described in
-/// [Zeller et al, 2002](https://doi.org/10.1109/32.988498).
+/// Reduces the current sequence of nodes, using the outcome of the `test_nodes` method of the
+/// `pass`. It returns the nodes that causes the failure and the source code of the minimal
+/// reproducible example.
 pub fn ddmin<'a>(
     seq: &[TSNode<'a>],
     pass: &impl Pass<'a>,
 ) -> Result<(Vec<TSNode<'a>>, String), Error> {
+    let base = Hasher::hash_str(pass.language().source());
+    ddmin_with(seq, pass, |removed| {
+        let edits = removed.iter().map(Edit::remove).collect::<Vec<_>>();
+        let source = crate::treesitter::apply_edits(pass.language().source(), &edits)?;
+        Ok(Candidate::with_edits(source, base, edits))
+    })
+}
+
+/// Reduces the program applying as many `edits` as possible, using the outcome of the
+/// `test_edits` method of the `pass`. It returns the edits that cannot be applied and the source
+/// code of the minimal reproducible example.
+pub fn ddmin_edits<'a>(edits: &[Edit], pass: &impl Pass<'a>) -> Result<(Vec<Edit>, String), Error> {
+    let base = Hasher::hash_str(pass.language().source());
+    ddmin_with(edits, pass, |applied| {
+        let source = crate::treesitter::apply_edits(pass.language().source(), applied)?;
+        Ok(Candidate::with_edits(source, base, applied.to_vec()))
+    })
+}
+
+/// Applies as many `edits` as possible as described in `ddmin_edits`. Unlike the delta debugging,
+/// it tries to apply all the edits first, so they are applied at once when none of them is
+/// required for the failure. Returns the source code of the minimal reproducible example.
+pub fn reduce_edits<'a>(edits: &[Edit], pass: &impl Pass<'a>) -> Result<String, Error> {
+    let base = Hasher::hash_str(pass.language().source());
+    let source = crate::treesitter::apply_edits(pass.language().source(), edits)?;
+    let candidate = Candidate::with_edits(source, base, edits.to_vec());
+    if pass
+        .find_failing(std::slice::from_ref(&candidate))?
+        .is_some()
+    {
+        pass.save_best(&candidate)?;
+        return Ok(candidate.source);
+    }
+    if edits.len() > 1 {
+        let (_, source) = ddmin_edits(edits, pass)?;
+        return Ok(source);
+    }
+    Ok(pass.source_code())
+}
+
+/// Splits `seq` into `n` subsets of almost equal size.
+fn split<T: Clone>(seq: &[T], n: usize) -> Vec<Vec<T>> {
+    (0..n)
+        .map(|i| seq[seq.len() * i / n..seq.len() * (i + 1) / n].to_vec())
+        .collect()
+}
+
+/// Returns the hash that identifies the sequence reduced from the `source` in the checkpoint.
+/// Besides the `source`, it hashes the candidate where `all` the elements are removed, so it is the
+/// same when the reduction is resumed from the smaller program.
+fn sequence_hash<T, F>(source: &str, all: &[T], candidate: &F) -> u64
+where
+    F: Fn(&[T]) -> Result<Candidate, Error>,
+{
+    let mut hasher = Hasher::new();
+    hasher.write_str(source);
+    if let Ok(c) = candidate(all) {
+        hasher.write_str(&c.source);
+        for file in c.removed_files.iter() {
+            hasher.write_str(file);
+        }
+    }
+    hasher.finish()
+}
+
+/// Reduces the sequence of arbitrary elements. The `candidate` function returns the program from
+/// which the given elements are removed.
+///
+/// This function implements the Minimizing Delta Debugging algorithm described in
+/// [Zeller et al, 2002](https://doi.org/10.1109/32.988498). On each iteration the sequence is
+/// split into `n` subsets, and the algorithm tries to:
+/// 1. Reduce to subset: keep only one of the subsets. This step can be disabled in the
+///    application configuration.
+/// 2. Reduce to complement: remove one of the subsets.
+/// 3. Increase granularity: split the sequence into `2n` subsets.
+///
+/// The candidates of each step are checked in batches of `jobs` candidates concurrently. The
+/// first failing candidate is always selected, so the result doesn't depend on `jobs`.
+///
+/// The granularity and the position in the steps are saved to the checkpoint before each batch.
+/// The resumed reduction continues from them if the pass reduces the same sequence of the same
+/// file.
+pub fn ddmin_with<'a, T, F>(
+    seq: &[T],
+    pass: &impl Pass<'a>,
+    candidate: F,
+) -> Result<(Vec<T>, String), Error>
+where
+    T: Clone + PartialEq + fmt::Debug,
+    F: Fn(&[T]) -> Result<Candidate, Error>,
+{
     let mut source_code = pass.source_code();
-    match pass.test_source(&source_code) {
-        Ok((TestOutcome::Pass, _)) => return Err(Error::NoChange),
-        Ok(_) => (),
-        Err(err) => return Err(Error::new(err.to_string())),
-    };
+    let (result, _) = pass.test_source(&source_code)?;
+    if !pass.context().is_interesting(&result) {
+        return Err(Error::NoChange);
+    }
 
+    let jobs = std::cmp::max(pass.app().jobs, 1);
     let mut granularity = 2;
+    // Number of the steps skipped on the first iteration.
+    let mut position = 0;
+    let target = pass.context().workspace().target().to_string();
+    let initial = DdminState {
+        pass: pass.name(),
+        target: target.clone(),
+        sequence: sequence_hash(&source_code, seq, &candidate),
+        length: seq.len(),
+        ..DdminState::default()
+    };
+    if let Some(state) = pass.context().take_resumed_ddmin(&initial) {
+        log::debug!("Resuming the delta debugging: {:?}", state);
+        granularity = std::cmp::max(state.granularity, 2);
+        position = state.position;
+    }
     let mut seq = seq.to_owned();
+    // Elements removed from the original sequence.
+    let mut removed: Vec<T> = vec![];
     while seq.len() >= 2 {
-        let mut start: usize = 0;
-        let subset_length: usize = seq.len() / 2;
-        let mut some_complement_is_failing = false;
-        while start < seq.len() {
-            // A complement is a sequence of nodes that will be removed during the test.
-            let complement = [&seq[..start], &seq[start + subset_length..]].concat();
-            log::debug!(
-                "Testing w/o sequence: {:#?}\nSource: {}",
-                complement
-                    .iter()
-                    .map(|n| { crate::treesitter::node_source(&pass.source_code(), n) })
-                    .collect::<Vec<String>>(),
-                source_code
-            );
-            if let Ok((TestOutcome::Fail, new_source)) = pass.test_nodes(&source_code, &complement)
-            {
-                remove_complement(&mut seq, &complement);
-                source_code = new_source;
-                log::debug!(
-                    "Reduced sequence: {:#?}\nNew source: {}",
-                    seq.iter()
-                        .map(|n| { crate::treesitter::node_source(&pass.source_code(), n) })
-                        .collect::<Vec<String>>(),
-                    source_code
-                );
-                granularity = std::cmp::max(granularity - 1, 2);
-                some_complement_is_failing = true;
-                break;
+        let subsets = split(&seq, granularity);
+        // Steps are the pairs of the elements that will be removed during the test and the
+        // granularity used on the next iteration if the candidate is failing.
+        let mut steps: Vec<(Vec<T>, usize)> = vec![];
+        if pass.app().subsets {
+            for i in 0..subsets.len() {
+                let complement = [&subsets[..i], &subsets[i + 1..]].concat().concat();
+                steps.push((complement, 2));
+            }
+        }
+        // For two subsets the complements are the same as the subsets.
+        if granularity > 2 || !pass.app().subsets {
+            for subset in subsets.iter() {
+                steps.push((subset.clone(), std::cmp::max(granularity - 1, 2)));
+            }
+        }
+
+        let sequence = sequence_hash(&source_code, &[&removed, &seq[..]].concat(), &candidate);
+        let mut some_candidate_is_failing = false;
+        let start = std::cmp::min(std::mem::take(&mut position), steps.len());
+        for (i, batch) in steps[start..].chunks(jobs).enumerate() {
+            pass.context().save_ddmin_state(
+                DdminState {
+                    pass: pass.name(),
+                    target: target.clone(),
+                    sequence,
+                    length: seq.len(),
+                    granularity,
+                    position: start + i * jobs,
+                },
+                steps.len(),
+            )?;
+            let mut candidates = vec![];
+            let mut batch_steps = vec![];
+            for step in batch.iter() {
+                log::debug!("Testing w/o sequence: {:#?}", step.0);
+                match candidate(&[&removed, &step.0[..]].concat()) {
+                    Ok(c) => {
+                        candidates.push(c);
+                        batch_steps.push(step);
+                    }
+                    Err(err) => log::debug!("Cannot create the candidate: {}", err),
+                }
+            }
+            if candidates.is_empty() {
+                continue;
             }
-            start += subset_length;
+            let failing = match pass.find_failing(&candidates)? {
+                Some(i) => i,
+                None => continue,
+            };
+            let (step, next_granularity) = batch_steps[failing];
+            remove_complement(&mut seq, step);
+            removed.extend(step.iter().cloned());
+            pass.save_best(&candidates[failing])?;
+            source_code = candidates.swap_remove(failing).source;
+            log::debug!("Reduced sequence: {:#?}\nNew source: {}", seq, source_code);
+            granularity = std::cmp::min(*next_granularity, std::cmp::max(seq.len(), 2));
+            some_candidate_is_failing = true;
+            break;
         }
-        if !some_complement_is_failing {
-            if granularity == seq.len() {
+        if !some_candidate_is_failing {
+            if granularity >= seq.len() {
                 break;
             }
             granularity = std::cmp::min(granularity * 2, seq.len());
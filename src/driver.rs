@@ -1,29 +1,48 @@
 use crate::app::App;
+use crate::config::Config;
+use crate::context::Context;
 use crate::error::Error;
-use crate::passes::imports::PassImports;
-use crate::passes::top::PassTop;
-use crate::passes::Pass;
+use crate::events::{self, Event};
+use crate::interrupt;
+use crate::passes::files::PassFiles;
+use crate::passes::{find_pass, PassInst, REGISTRY};
+use crate::progress::Size;
+use crate::trace;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::{fs, path};
 
 mod rc {
     pub const SUCCESS: i32 = 0;
     pub const FAILURE: i32 = 1;
+    /// The original input doesn't reproduce the failure.
+    pub const NOT_INTERESTING: i32 = 2;
+    /// The script reproduces the failure for an empty input.
+    pub const ALWAYS_INTERESTING: i32 = 3;
+    /// The script cannot be executed.
+    pub const ORACLE_FAILED: i32 = 4;
+    /// The reduction has been stopped by SIGINT or SIGTERM.
+    pub const INTERRUPTED: i32 = 130;
 }
 
-type PassInst<'a> = Rc<RefCell<dyn Pass<'a> + 'a>>;
-
 fn create_dir(path: &str) -> Result<(), String> {
     match fs::create_dir_all(path) {
-        Err(err) => return Err(format!("Cannot create directory '{}': {}", path, err,)),
+        Err(err) => Err(format!("Cannot create directory '{}': {}", path, err,)),
         Ok(_) => Ok(()),
     }
 }
 
 /// Creates the required temporary directories.
-fn prepare_out_dirs<'a>(app: &App, passes: &[PassInst<'a>]) -> Result<(), String> {
-    if path::Path::new(&app.output_dir).exists() {
+fn prepare_out_dirs<'a, 'p>(
+    app: &App,
+    passes: impl Iterator<Item = &'p PassInst<'a>>,
+) -> Result<(), String>
+where
+    'a: 'p,
+{
+    // The resumed reduction continues in the existing directory.
+    if path::Path::new(&app.output_dir).exists() && !app.resume {
         if app.force {
             if let Err(err) = fs::remove_dir_all(&app.output_dir) {
                 return Err(format!(
@@ -43,62 +62,370 @@ fn prepare_out_dirs<'a>(app: &App, passes: &[PassInst<'a>]) -> Result<(), String
     create_dir(&app.output_dir)?;
 
     // Create directories for the passes.
-    for p in passes.iter() {
+    for p in passes {
         create_dir(&p.borrow().temp_dir())?;
     }
 
     Ok(())
 }
 
-/// Runs application with the given configuration. Returns reduced source on success.
-pub fn run_app<'a>(app: &'a App) -> Result<String, Error> {
-    let mut passes: Vec<PassInst<'a>> = vec![];
-    if app.passes.imports {
-        match PassImports::from_app(app) {
-            Ok(p) => passes.push(Rc::new(RefCell::new(p))),
+/// States of the workspace left by the passes for each reduced file. They are used to skip the
+/// passes when the workspace was not changed since their last run, or was changed only by the
+/// passes after which they are not worth re-running. The passes are identified by their
+/// configuration, so the same pass repeated in the pipeline is not executed twice for the same
+/// input.
+#[derive(Default)]
+struct LastRuns {
+    /// State of the workspace after the last run of the pass for the file, and the number of the
+    /// changes made by then.
+    runs: HashMap<(String, String), (u64, usize)>,
+    /// Names of the passes that changed the workspace in the order of the changes.
+    changes: Vec<String>,
+}
+
+/// Runs the `pass` for the file which is reduced at the moment unless it is not needed. Returns
+/// true if the pass has been executed and the input reproduces the failure.
+fn run_pass<'a>(
+    ctx: &Context<'a>,
+    name: &str,
+    pass: &PassInst<'a>,
+    last_runs: &mut LastRuns,
+) -> Result<bool, Error> {
+    interrupt::check()?;
+    let name = name.to_string();
+    let target = ctx.workspace().target().to_string();
+    let source = ctx.workspace().target_source();
+    let before = ctx.candidate_key(&source, &[]);
+    match last_runs.runs.get(&(name.clone(), target.clone())) {
+        Some((state, _)) if *state == before => {
+            log::debug!("Skipping {} for {}: nothing changed", name, target);
+            return Ok(false);
+        }
+        Some((_, seen))
+            if !last_runs.changes[*seen..]
+                .iter()
+                .any(|p| pass.borrow().rerun_after(p)) =>
+        {
+            log::debug!("Skipping {} for {}: not worth re-running", name, target);
+            return Ok(false);
+        }
+        _ => (),
+    }
+    ctx.start_pass(&name)?;
+    let reproduced = match pass.borrow_mut().run(Some(&source)) {
+        Ok(reduced_source) => {
+            log::debug!("Reduced source: {}", &reduced_source);
+            ctx.workspace_mut().update(&reduced_source);
+            true
+        }
+        Err(Error::NoChange) => {
+            log::debug!("Source code has not been reduced");
+            false
+        }
+        Err(err) => return Err(err),
+    };
+    ctx.finish_pass(reproduced)?;
+    let state = ctx.candidate_key(&ctx.workspace().target_source(), &[]);
+    if state != before {
+        // The registry name of the pass without its options.
+        let base = name.split(':').next().unwrap_or(&name).to_string();
+        last_runs.changes.push(base);
+    }
+    let seen = last_runs.changes.len();
+    last_runs.runs.insert((name, target), (state, seen));
+    ctx.checkpoint().reproduced |= reproduced;
+    Ok(reproduced)
+}
+
+/// Point of the resumed reduction, where it was interrupted: the pass and the reduced file.
+type ResumePoint = Option<(String, String)>;
+
+/// Returns true if the `pass` should be executed for the reduced file. The passes that precede
+/// the `resume_point` are skipped. When the resume point is reached, the delta debugging in the
+/// pass continues from the state saved in the checkpoint.
+fn reached(ctx: &Context, resume_point: &mut ResumePoint, pass: &str) -> bool {
+    match resume_point {
+        None => true,
+        Some((p, target)) if p == pass && target == ctx.workspace().target() => {
+            log::info!("Resuming {} for {}", pass, target);
+            *resume_point = None;
+            let state = ctx.checkpoint().ddmin.clone();
+            ctx.resume_ddmin(state);
+            true
+        }
+        Some(_) => false,
+    }
+}
+
+/// Passes of the pipeline with their configurations, e.g. `hdd:max-depth=3`.
+pub(crate) type Pipeline<'a> = Vec<(String, PassInst<'a>)>;
+
+/// Creates the passes of the pipeline given in the application configuration.
+pub(crate) fn create_passes<'a>(ctx: &Rc<Context<'a>>) -> Result<Pipeline<'a>, Error> {
+    let mut passes: Pipeline<'a> = vec![];
+    for config in ctx.app().passes.iter() {
+        let info = match find_pass(&config.name) {
+            Some(info) => info,
+            None => return Err(Error::new(format!("Unknown pass: {}", config.name))),
+        };
+        match (info.create)(ctx.clone(), config) {
+            Ok(p) => passes.push((config.to_string(), p)),
             Err(err) => {
                 return Err(Error::new(format!(
-                    "Cannot initialize PassImports pass: {}",
-                    err
+                    "Cannot initialize {} pass: {}",
+                    config.name, err
                 )))
             }
         }
     }
-    if app.passes.top {
-        match PassTop::from_app(app) {
-            Ok(p) => passes.push(Rc::new(RefCell::new(p))),
+    Ok(passes)
+}
+
+/// Runs application with the given configuration. Returns reduced source on success.
+pub fn run_app(app: &App) -> Result<String, Error> {
+    let ctx = Rc::new(Context::new(app)?);
+    let passes = create_passes(&ctx)?;
+    let files_pass: Option<PassInst> = if app.recursive {
+        match PassFiles::new(ctx.clone()) {
+            Ok(p) => Some(Rc::new(RefCell::new(p))),
             Err(err) => {
                 return Err(Error::new(format!(
-                    "Cannot initialize PassTop pass: {}",
+                    "Cannot initialize PassFiles pass: {}",
                     err
                 )))
             }
         }
+    } else {
+        None
+    };
+
+    prepare_out_dirs(app, passes.iter().map(|p| &p.1).chain(files_pass.iter()))?;
+    // The output directory might be recreated, so the loaded entries are written again.
+    ctx.cache().save()?;
+    ctx.open_trace()?;
+
+    let initial_size = start_progress(&ctx).bytes;
+    // The resumed reduction has been checked before the interruption.
+    let checked = if app.resume {
+        Ok(())
+    } else {
+        check_input(&ctx)
+    };
+    let reproduced = match checked.and_then(|_| reduce(&ctx, &passes, files_pass.as_ref())) {
+        Err(Error::Interrupted) => {
+            save_interrupted(&ctx)?;
+            write_summary(&ctx, "interrupted", initial_size)?;
+            return Err(Error::Interrupted);
+        }
+        result => result?,
+    };
+    log::info!(
+        "Cache: {} hits, {} misses",
+        ctx.cache().hits(),
+        ctx.cache().misses()
+    );
+    let flaky = ctx.flaky().len();
+    if flaky > 0 {
+        log::warn!(
+            "Found {} flaky candidates, see {}",
+            flaky,
+            app.summary_file()
+        );
+    }
+    if !reproduced {
+        write_summary(&ctx, "not reproduced", initial_size)?;
+        return Err(Error::NoChange);
     }
+    write_summary(&ctx, "finished", initial_size)?;
 
-    prepare_out_dirs(app, &passes)?;
+    ctx.save_result()?;
+    let workspace = ctx.workspace();
+    Ok(workspace.source(workspace.main()).unwrap_or("").to_string())
+}
 
-    let mut source: Option<String> = None;
-    for p in passes.iter() {
-        let result = match &source {
-            Some(s) => p.borrow_mut().run(Some(s)),
-            None => p.borrow_mut().run(None),
-        };
-        match result {
-            Ok(reduced_source) => {
-                log::debug!("Reduced source: {}", &reduced_source);
-                source = Some(reduced_source);
+/// Starts measuring the reduction from the size of the original program and returns it. The
+/// resumed reduction keeps the size saved in the checkpoint.
+pub(crate) fn start_progress(ctx: &Context) -> Size {
+    let size = ctx.workspace().measure();
+    let saved = ctx.checkpoint().initial;
+    let initial = match saved {
+        Some(initial) if ctx.app().resume => initial,
+        _ => size,
+    };
+    ctx.checkpoint().initial = Some(initial);
+    ctx.progress().start(initial, size);
+    initial
+}
+
+/// Checks that the original input reproduces the failure and, if requested, that an empty input
+/// doesn't reproduce it. The original input is saved as the smallest failing program, so it is
+/// the result when the passes cannot reduce it.
+pub(crate) fn check_input(ctx: &Context) -> Result<(), Error> {
+    let source = ctx.workspace().target_source();
+    let (outcome, report) = ctx.probe("original", &source)?;
+    log::debug!("Original input: {}", &outcome);
+    ctx.cache()
+        .insert(ctx.candidate_key(&source, &[]), &outcome)?;
+    if !ctx.is_interesting(&outcome) {
+        return Err(Error::NotInteresting(report));
+    }
+    if ctx.app().check_empty {
+        let (outcome, report) = ctx.probe("empty", "")?;
+        log::debug!("Empty input: {}", &outcome);
+        if ctx.is_interesting(&outcome) {
+            return Err(Error::AlwaysInteresting(report));
+        }
+    }
+    ctx.save_best(&source, &[])
+}
+
+/// Runs the passes in rounds until they reduce the program. Returns true if the failure has been
+/// reproduced.
+pub(crate) fn reduce<'a>(
+    ctx: &Context<'a>,
+    passes: &[(String, PassInst<'a>)],
+    files_pass: Option<&PassInst<'a>>,
+) -> Result<bool, Error> {
+    let app = ctx.app();
+    let mut resume_point: ResumePoint = None;
+    if app.resume {
+        let checkpoint = ctx.checkpoint();
+        resume_point = checkpoint.pass.clone().zip(checkpoint.target.clone());
+    }
+    let mut reproduced = ctx.checkpoint().reproduced;
+    let mut last_runs = LastRuns::default();
+    let mut round = ctx.checkpoint().round;
+    loop {
+        if matches!(app.max_rounds, Some(max) if round >= max) {
+            log::info!("Stopping after {} rounds", round);
+            break;
+        }
+        round += 1;
+        ctx.progress().start_round(round);
+        let size = ctx.workspace().size();
+        let resumed_round = resume_point.is_some();
+
+        // Remove the whole files first, then reduce the remaining ones starting from the main
+        // file.
+        if let Some(p) = files_pass {
+            let main = ctx.workspace().main().to_string();
+            ctx.workspace_mut().set_target(&main);
+            if reached(ctx, &mut resume_point, "files") {
+                reproduced |= run_pass(ctx, "files", p, &mut last_runs)?;
             }
-            Err(Error::NoChange) => log::debug!("Source code has not been reduced"),
-            Err(err) => return Err(err),
-        };
+        }
+        let files = ctx.workspace().lua_files();
+        for file in files.iter() {
+            if !ctx.workspace().contains(file) {
+                continue;
+            }
+            log::debug!("Reducing {}", file);
+            ctx.workspace_mut().set_target(file);
+            for (name, p) in passes.iter() {
+                if reached(ctx, &mut resume_point, name) {
+                    reproduced |= run_pass(ctx, name, p, &mut last_runs)?;
+                }
+            }
+        }
+        ctx.checkpoint().round = round;
+
+        let new_size = ctx.workspace().size();
+        log::info!(
+            "Round {}: reduced from {} to {} bytes",
+            round,
+            size,
+            new_size
+        );
+        // The passes executed before the interruption might have reduced the program, so the
+        // resumed round is always followed by the next one.
+        if new_size >= size && !resumed_round {
+            break;
+        }
+        resume_point = None;
     }
+    Ok(reproduced)
+}
 
-    source.ok_or(Error::NoChange)
+/// Saves the smallest failing program found before the interruption. The passes keep it in the
+/// checkpoint, because the workspace is updated only when they finish.
+fn save_interrupted(ctx: &Context) -> Result<(), Error> {
+    let checkpoint = ctx.checkpoint().clone();
+    if !checkpoint.reproduced {
+        return Ok(());
+    }
+    ctx.workspace_mut().restore(checkpoint.files)?;
+    let source = ctx.workspace().target_source();
+    ctx.save_best(&source, &[])
+}
+
+/// Writes the summary of the reduction to the output directory and shows the statistics of the
+/// passes.
+fn write_summary(ctx: &Context, status: &str, initial_size: usize) -> Result<(), Error> {
+    ctx.progress().finish();
+    let checkpoint = ctx.checkpoint().clone();
+    let mut summary = format!(
+        "Status: {}\nCompleted rounds: {}\n",
+        status, checkpoint.round
+    );
+    if let (Some(pass), Some(target)) = (&checkpoint.pass, &checkpoint.target) {
+        summary.push_str(&format!("Last pass: {} for {}\n", pass, target));
+    }
+    summary.push_str(&format!(
+        "Size: {} -> {} bytes\nCache: {} hits, {} misses\n",
+        initial_size,
+        ctx.workspace().size(),
+        ctx.cache().hits(),
+        ctx.cache().misses()
+    ));
+    if checkpoint.reproduced {
+        summary.push_str(&format!("Best: {}\n", ctx.app().best_file()));
+    }
+    let flaky = ctx.flaky();
+    if !flaky.is_empty() {
+        summary.push_str(&format!("Flaky candidates: {}\n", flaky.len()));
+        for f in flaky.iter() {
+            let outcomes = f.outcomes.iter().map(|o| o.to_string());
+            summary.push_str(&format!(
+                "  {}: {}\n",
+                f.target,
+                outcomes.collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+    summary.push_str(&ctx.progress().to_string());
+    let best = ctx.app().best_file();
+    ctx.events().emit(Event::Result {
+        status,
+        size: ctx.workspace().size(),
+        best: checkpoint.reproduced.then_some(best.as_str()),
+    })?;
+    let path = ctx.app().summary_file();
+    fs::write(&path, summary).map_err(|err| Error::new(format!("{}: {}", path, err)))
+}
+
+/// Prints the passes available in the pipeline and their options.
+fn print_passes() {
+    for info in REGISTRY.iter() {
+        println!("{:<12} {}", info.name, info.description);
+        for option in info.options.iter() {
+            println!("  {:<10} {}", option.name, option.description);
+        }
+    }
+}
+
+/// Initializes the logger. The verbosity sets the level of the messages unless it is given in
+/// the `RUST_LOG` environment variable.
+fn init_logger(verbosity: u8) {
+    let level = match verbosity {
+        0 => "error",
+        1 => "warn",
+        2 => "info",
+        _ => "debug",
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level)).init();
 }
 
 pub fn run() -> i32 {
-    env_logger::init();
     let app = match App::from_args() {
         Ok(app) => app,
         Err(err) => {
@@ -106,12 +433,72 @@ pub fn run() -> i32 {
             return rc::FAILURE;
         }
     };
+    init_logger(app.verbosity);
+    if let Err(err) = interrupt::install_handler() {
+        eprintln!("{}", err);
+        return rc::FAILURE;
+    }
+    if app.list_passes {
+        print_passes();
+        return rc::SUCCESS;
+    }
+    if let Some(trace) = &app.replay {
+        return match trace::replay(trace) {
+            Ok(source) => {
+                print!("{}", source);
+                rc::SUCCESS
+            }
+            Err(Error::Interrupted) => rc::INTERRUPTED,
+            Err(err) => {
+                eprintln!("{}", err);
+                rc::FAILURE
+            }
+        };
+    }
+    if app.dump_config {
+        return match Config::from_app(&app).to_toml() {
+            Ok(config) => {
+                print!("{}", config);
+                rc::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                rc::FAILURE
+            }
+        };
+    }
     match run_app(&app) {
-        Ok(_) => rc::SUCCESS,
-        Err(Error::NoChange) => {
-            println!("Cannot reproduce the failure");
+        Ok(source) => {
+            // The events written to stdout are not mixed with the program.
+            let events_to_stdout = app.events.as_deref() == Some(events::STDOUT);
+            if app.result.is_none() && !app.in_place && !events_to_stdout {
+                print!("{}", source);
+            }
             rc::SUCCESS
         }
+        Err(Error::NoChange) => {
+            eprintln!("Cannot reproduce the failure");
+            rc::NOT_INTERESTING
+        }
+        Err(err @ Error::NotInteresting(_)) => {
+            eprintln!("{}", err);
+            rc::NOT_INTERESTING
+        }
+        Err(err @ Error::AlwaysInteresting(_)) => {
+            eprintln!("{}", err);
+            rc::ALWAYS_INTERESTING
+        }
+        Err(err @ Error::OracleFailed(_)) => {
+            eprintln!("{}", err);
+            rc::ORACLE_FAILED
+        }
+        Err(Error::Interrupted) => {
+            eprintln!(
+                "Interrupted. The summary is saved to {}",
+                app.summary_file()
+            );
+            rc::INTERRUPTED
+        }
         Err(err) => {
             eprintln!("{}", err);
             rc::FAILURE
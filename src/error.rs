@@ -5,6 +5,16 @@ use std::fmt;
 pub enum Error {
     /// The given input did not cause a failure.
     NoChange,
+    /// The original input doesn't reproduce the failure. Contains the report of the script
+    /// execution.
+    NotInteresting(String),
+    /// The script reproduces the failure for an empty input. Contains the report of the script
+    /// execution.
+    AlwaysInteresting(String),
+    /// The script cannot be executed.
+    OracleFailed(String),
+    /// The reduction has been interrupted by a signal.
+    Interrupted,
     /// Other kinds of errors propagated from other Result errors.
     Error(String),
 }
@@ -24,6 +34,18 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::NoChange => write!(f, "input did not cause a failure"),
+            Error::NotInteresting(report) => write!(
+                f,
+                "The original input doesn't reproduce the failure\n{}",
+                report
+            ),
+            Error::AlwaysInteresting(report) => write!(
+                f,
+                "The script reproduces the failure for an empty input\n{}",
+                report
+            ),
+            Error::OracleFailed(report) => write!(f, "Cannot execute the script: {}", report),
+            Error::Interrupted => write!(f, "interrupted"),
             Error::Error(s) => write!(f, "{}", s),
         }
     }
@@ -0,0 +1,147 @@
+//! Events writes the stream of the reduction events in the JSON Lines format, so the reduction
+//! may be visualized and audited by external tools. Each line is an object with the `event` type
+//! and the `time` in seconds since the start of the reduction:
+//!
+//! ```json
+//! {"event":"pass_started","round":1,"pass":"top","target":"bug.lua","size":120,"time":0.01}
+//! {"event":"candidate_tested","id":3,"pass":"top","file":"/tmp/dd/top/3","size":64,"outcome":"fail","cached":false,"duration":0.18,"time":0.2}
+//! {"event":"candidate_accepted","id":3,"pass":"top","size":64,"time":0.2}
+//! {"event":"pass_finished","pass":"top","target":"bug.lua","reduced":true,"size":64,"time":0.5}
+//! {"event":"result","status":"finished","size":64,"best":"/tmp/dd/best.lua","time":0.5}
+//! ```
+//!
+//! The candidates are identified by the numbers of their temporary files.
+use crate::error::Error;
+use crate::passes::TestOutcome;
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::time::Instant;
+
+/// Path that means the standard output.
+pub const STDOUT: &str = "-";
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'e> {
+    PassStarted {
+        round: usize,
+        pass: &'e str,
+        target: &'e str,
+        /// Size of the program in bytes.
+        size: usize,
+    },
+    CandidateTested {
+        id: usize,
+        pass: &'e str,
+        /// Path given to the script. It is absent for the cached candidates.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<&'e str>,
+        /// Size of the reduced file in bytes.
+        size: usize,
+        outcome: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<&'e str>,
+        cached: bool,
+        /// Time of the script execution in seconds.
+        duration: f64,
+    },
+    CandidateAccepted {
+        id: usize,
+        pass: &'e str,
+        size: usize,
+    },
+    PassFinished {
+        pass: &'e str,
+        target: &'e str,
+        reduced: bool,
+        size: usize,
+    },
+    Result {
+        status: &'e str,
+        size: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        best: Option<&'e str>,
+    },
+}
+
+/// Event with the time it occurred at.
+#[derive(Serialize)]
+struct Record<'e> {
+    #[serde(flatten)]
+    event: Event<'e>,
+    time: f64,
+}
+
+/// Returns the name of the `outcome` and the reason of the unresolved one. The candidates whose
+/// scripts were killed after another candidate reproduced the failure have no outcome.
+pub fn outcome_name(outcome: Option<&TestOutcome>) -> (&'static str, Option<&str>) {
+    match outcome {
+        Some(TestOutcome::Pass) => ("pass", None),
+        Some(TestOutcome::Fail) => ("fail", None),
+        Some(TestOutcome::Timeout) => ("timeout", None),
+        Some(TestOutcome::Unresolved(reason)) => ("unresolved", Some(reason)),
+        None => ("cancelled", None),
+    }
+}
+
+pub struct Events {
+    /// Path to the file, or `-` for the standard output. The events are not written if it is
+    /// `None`.
+    path: Option<String>,
+    /// True if the events are appended to the existing file.
+    append: bool,
+    /// The output is opened with the first event, after the output directory is prepared.
+    out: Option<Box<dyn Write>>,
+    started: Instant,
+}
+
+impl Events {
+    pub fn new(path: Option<String>, append: bool) -> Self {
+        Events {
+            path,
+            append,
+            out: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns true if the events are written.
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    fn open(path: &str, append: bool) -> Result<Box<dyn Write>, Error> {
+        if path == STDOUT {
+            return Ok(Box::new(std::io::stdout()));
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|err| Error::new(format!("{}: {}", path, err)))?;
+        Ok(Box::new(std::io::BufWriter::new(file)))
+    }
+
+    /// Writes the `event`.
+    pub fn emit(&mut self, event: Event) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if self.out.is_none() {
+            self.out = Some(Events::open(path, self.append)?);
+        }
+        let record = Record {
+            event,
+            time: self.started.elapsed().as_secs_f64(),
+        };
+        let line = serde_json::to_string(&record).map_err(|err| Error::new(err.to_string()))?;
+        let out = self.out.as_mut().unwrap();
+        writeln!(out, "{}", line)
+            .and_then(|_| out.flush())
+            .map_err(|err| Error::new(format!("{}: {}", path, err)))
+    }
+}
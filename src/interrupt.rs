@@ -0,0 +1,27 @@
+//! Interrupt handles SIGINT and SIGTERM. The handler only sets a flag: the running scripts are
+//! killed by the oracle, and the error is propagated to the driver that saves the smallest
+//! failing program found so far.
+use crate::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the handler of SIGINT and SIGTERM.
+pub fn install_handler() -> Result<(), Error> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+        .map_err(|err| Error::new(format!("Cannot set the signal handler: {}", err)))
+}
+
+/// Returns true if the reduction has been interrupted.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Returns `Error::Interrupted` if the reduction has been interrupted.
+pub fn check() -> Result<(), Error> {
+    if is_interrupted() {
+        Err(Error::Interrupted)
+    } else {
+        Ok(())
+    }
+}
@@ -1,6 +1,17 @@
 pub mod app;
+pub mod cache;
+pub mod checkpoint;
+pub mod config;
+pub mod context;
 pub mod delta;
 pub mod driver;
 pub mod error;
+pub mod events;
+pub mod interrupt;
+pub mod oracle;
 pub mod passes;
+pub mod progress;
+pub mod reducer;
+pub mod trace;
 pub mod treesitter;
+pub mod workspace;
@@ -0,0 +1,560 @@
+//! Oracle decides whether a candidate reproduces the failure. It runs the check command for the
+//! candidate and matches its exit status and output against the criteria from the application
+//! configuration.
+//!
+//! Without any criteria a candidate is interesting when the command fails, i.e. returns a
+//! non-zero exit code or gets killed. Expected exit codes and the signal replace this rule. The
+//! regular expressions add more conditions to the expected exit codes and the signal, but when
+//! neither of them is set, the regular expressions decide on their own and the exit status of the
+//! command is ignored. The command that exceeds the timeout is killed together with the
+//! processes started by it, and its outcome is `TestOutcome::Timeout`, which is not interesting
+//! unless the application configuration says otherwise.
+//!
+//! The script may return the exit code 125 to skip the candidate, as in `git bisect run`. Such
+//! outcomes are unresolved and may be retried. The interesting outcomes may be confirmed by
+//! running the script again: if the outcomes differ, the candidate is flaky. It is skipped and
+//! reported at the end of the reduction.
+//!
+//! The library users may check the candidates in-process implementing the `Oracle` trait instead
+//! of running a script, see `reducer::Reducer`.
+use crate::app::App;
+use crate::cache::Hasher;
+use crate::error::Error;
+use crate::interrupt;
+use crate::passes::TestOutcome;
+use regex::Regex;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+/// Placeholder replaced with the path to the candidate in the command line.
+const PLACEHOLDER: &str = "{}";
+
+/// Exit code of the script that means that the candidate cannot be tested.
+const UNRESOLVED_CODE: i32 = 125;
+
+/// Exit codes returned by the shell when the command is not executable or not found.
+const NOT_EXECUTED_CODES: [i32; 2] = [126, 127];
+
+/// A candidate for which the script returned different outcomes.
+#[derive(Debug, Clone)]
+pub struct Flaky {
+    /// Path given to the script.
+    pub target: String,
+    pub outcomes: Vec<TestOutcome>,
+}
+
+/// Result of the command executed for a candidate.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub outcome: TestOutcome,
+    /// Time spent on the executions of the command including the retries.
+    pub duration: Duration,
+}
+
+impl Execution {
+    fn not_executed() -> Self {
+        Execution {
+            outcome: TestOutcome::Unresolved("The command was not executed".to_string()),
+            duration: Duration::ZERO,
+        }
+    }
+}
+
+/// Result of the command executed by `ScriptOracle::probe`.
+pub struct Probe {
+    pub outcome: TestOutcome,
+    /// Exit status of the command. It is `None` if the command exceeded the timeout.
+    pub status: Option<ExitStatus>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.status {
+            Some(status) => writeln!(f, "{}", status)?,
+            None => writeln!(f, "timeout")?,
+        }
+        writeln!(f, "Outcome: {}", self.outcome)?;
+        writeln!(f, "Stdout:\n{}", self.stdout)?;
+        write!(f, "Stderr:\n{}", self.stderr)
+    }
+}
+
+/// Checks in-process whether the candidate reproduces the failure.
+pub trait Oracle {
+    /// Returns the outcome of the check for the source code of the `candidate`. The `Fail`
+    /// outcome means that the candidate reproduces the failure.
+    fn test(&self, candidate: &str) -> TestOutcome;
+}
+
+impl<F> Oracle for F
+where
+    F: Fn(&str) -> TestOutcome,
+{
+    fn test(&self, candidate: &str) -> TestOutcome {
+        self(candidate)
+    }
+}
+
+/// Oracle that runs the check command for the candidates written to disk.
+pub struct ScriptOracle {
+    /// The program and its arguments.
+    command: Vec<String>,
+    timeout: Option<u32>,
+    timeout_interesting: bool,
+    retries: usize,
+    confirm: usize,
+    exit_codes: Vec<i32>,
+    signal: Option<i32>,
+    stdout_regex: Option<Regex>,
+    stdout_not_regex: Option<Regex>,
+    stderr_regex: Option<Regex>,
+    stderr_not_regex: Option<Regex>,
+    flaky: RefCell<Vec<Flaky>>,
+}
+
+/// Splits the command line into arguments. The arguments may be quoted with single or double
+/// quotes, and a backslash escapes the next character outside of the quotes.
+fn split_command(line: &str) -> Result<Vec<String>, Error> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    arg.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(a) = arg.take() {
+                    args.push(a);
+                }
+            }
+            (None, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(Error::new(format!(
+            "Unterminated quote in the command: {}",
+            line
+        )));
+    }
+    args.extend(arg);
+    if args.is_empty() {
+        return Err(Error::new("The check command is empty"));
+    }
+    Ok(args)
+}
+
+fn compile(regex: &Option<String>) -> Result<Option<Regex>, Error> {
+    match regex {
+        Some(r) => match Regex::new(r) {
+            Ok(regex) => Ok(Some(regex)),
+            Err(err) => Err(Error::new(format!(
+                "Invalid regular expression '{}': {}",
+                r, err
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns path to the file that keeps the `stream` of the command executed for the `target`.
+fn output_file(target: &str, stream: &str) -> String {
+    format!("{}.{}", target.trim_end_matches('/'), stream)
+}
+
+/// Returns the signal that killed the process.
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Returns the content of the `stream` written by the command executed for the `target`.
+fn read_output(target: &str, stream: &str) -> String {
+    let file = output_file(target, stream);
+    match fs::read(&file) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(err) => format!("{}: {}", file, err),
+    }
+}
+
+/// Kills the child process together with the processes started by it and waits for it. The
+/// child is always waited for, so it doesn't remain a zombie.
+fn kill(child: &mut Child) -> Result<ExitStatus, std::io::Error> {
+    // The child is the leader of its own process group, see `ScriptOracle::spawn`.
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    // The child is already dead if it has exited or has been killed with its group.
+    let killed = match child.kill() {
+        Err(err)
+            if err.kind() != std::io::ErrorKind::InvalidInput
+                && err.raw_os_error() != Some(libc::ESRCH) =>
+        {
+            Err(err)
+        }
+        _ => Ok(()),
+    };
+    let status = child.wait()?;
+    killed.map(|_| status)
+}
+
+impl ScriptOracle {
+    pub fn from_app(app: &App) -> Result<Self, Error> {
+        // The path to an existing script is not split, so it may contain spaces.
+        let command = if Path::new(&app.script).is_file() {
+            vec![app.script.clone()]
+        } else {
+            split_command(&app.script)?
+        };
+        Ok(ScriptOracle {
+            command,
+            timeout: app.timeout,
+            timeout_interesting: app.timeout_interesting,
+            retries: app.retries,
+            confirm: app.confirm,
+            exit_codes: app.exit_codes.clone(),
+            signal: app.signal,
+            stdout_regex: compile(&app.stdout_regex)?,
+            stdout_not_regex: compile(&app.stdout_not_regex)?,
+            stderr_regex: compile(&app.stderr_regex)?,
+            stderr_not_regex: compile(&app.stderr_not_regex)?,
+            flaky: RefCell::new(vec![]),
+        })
+    }
+
+    /// Feeds the command and the criteria to the `hasher`, because the outcomes depend on them.
+    pub fn hash_into(&self, hasher: &mut Hasher) {
+        for arg in self.command.iter() {
+            hasher.write_str(arg);
+        }
+        hasher.write_str(&format!(
+            "{:?} {:?} {:?}",
+            self.timeout, self.exit_codes, self.signal
+        ));
+        for regex in [
+            &self.stdout_regex,
+            &self.stdout_not_regex,
+            &self.stderr_regex,
+            &self.stderr_not_regex,
+        ] {
+            hasher.write_str(&format!("{:?}", regex.as_ref().map(|r| r.as_str())));
+        }
+    }
+
+    /// Starts the command for the `target`. The target is appended to the arguments if the
+    /// command line doesn't contain the placeholder. The output of the command is written next
+    /// to the target. The command is started in a new process group, so it doesn't receive the
+    /// signals sent to `dd` from the terminal, and all its processes may be killed at once.
+    fn spawn(&self, target: &str) -> Result<Child, std::io::Error> {
+        let args = &self.command[1..];
+        let mut command = Command::new(&self.command[0]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        command.args(args.iter().map(|a| a.replace(PLACEHOLDER, target)));
+        if !args.iter().any(|a| a.contains(PLACEHOLDER)) {
+            command.arg(target);
+        }
+        command
+            .stdin(Stdio::null())
+            .stdout(fs::File::create(output_file(target, "stdout"))?)
+            .stderr(fs::File::create(output_file(target, "stderr"))?)
+            .spawn()
+    }
+
+    /// Returns true if the command started at `started` exceeded the timeout.
+    fn timed_out(&self, started: Instant) -> bool {
+        matches!(self.timeout, Some(t) if started.elapsed() >= Duration::from_secs(t as u64))
+    }
+
+    /// Returns true if the output of the command should be checked.
+    fn checks_output(&self) -> bool {
+        self.stdout_regex.is_some()
+            || self.stdout_not_regex.is_some()
+            || self.stderr_regex.is_some()
+            || self.stderr_not_regex.is_some()
+    }
+
+    /// Returns TestOutcome::Fail if the command executed for the `target` reproduces the failure.
+    fn outcome(&self, target: &str, status: ExitStatus) -> TestOutcome {
+        if status.code() == Some(UNRESOLVED_CODE) && !self.exit_codes.contains(&UNRESOLVED_CODE) {
+            return TestOutcome::Unresolved(format!("Exit code {}", UNRESOLVED_CODE));
+        }
+        let status_matches = if self.exit_codes.is_empty() && self.signal.is_none() {
+            !status.success() || self.checks_output()
+        } else {
+            matches!(status.code(), Some(code) if self.exit_codes.contains(&code))
+                || (self.signal.is_some() && signal(&status) == self.signal)
+        };
+        if !status_matches {
+            return TestOutcome::Pass;
+        }
+        let criteria = [
+            ("stdout", &self.stdout_regex, true),
+            ("stdout", &self.stdout_not_regex, false),
+            ("stderr", &self.stderr_regex, true),
+            ("stderr", &self.stderr_not_regex, false),
+        ];
+        for (stream, regex, expected) in criteria {
+            let regex = match regex {
+                Some(regex) => regex,
+                None => continue,
+            };
+            let file = output_file(target, stream);
+            let output = match fs::read(&file) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(err) => return TestOutcome::Unresolved(format!("{}: {}", file, err)),
+            };
+            if regex.is_match(&output) != expected {
+                return TestOutcome::Pass;
+            }
+        }
+        TestOutcome::Fail
+    }
+
+    /// Returns true if the `outcome` reproduces the failure.
+    pub fn is_interesting(&self, outcome: &TestOutcome) -> bool {
+        match outcome {
+            TestOutcome::Fail => true,
+            TestOutcome::Timeout => self.timeout_interesting,
+            _ => false,
+        }
+    }
+
+    /// Returns the candidates for which the script returned different outcomes.
+    pub fn flaky(&self) -> Vec<Flaky> {
+        self.flaky.borrow().clone()
+    }
+
+    /// Applies the retry policy to the `execution` of the command for the `target`. The
+    /// unresolved outcome is retried, and the interesting one is confirmed by running the command
+    /// again. Returns the final outcome, which is unresolved for the flaky candidates.
+    fn settle(&self, target: &str, mut execution: Execution) -> Execution {
+        let run_again = || match self.execute(&[target]).pop() {
+            Some(Some(result)) => result,
+            _ => Execution::not_executed(),
+        };
+        let mut retries = 0;
+        while matches!(execution.outcome, TestOutcome::Unresolved(_))
+            && retries < self.retries
+            && !interrupt::is_interrupted()
+        {
+            log::debug!("File: {} Retrying: {}", target, &execution.outcome);
+            let duration = execution.duration;
+            execution = run_again();
+            execution.duration += duration;
+            retries += 1;
+        }
+        if !self.is_interesting(&execution.outcome) {
+            return execution;
+        }
+        let mut outcomes = vec![execution.outcome.clone()];
+        for _ in 0..self.confirm {
+            if interrupt::is_interrupted() {
+                break;
+            }
+            let again = run_again();
+            execution.duration += again.duration;
+            outcomes.push(again.outcome);
+            if outcomes.last() != Some(&execution.outcome) {
+                log::warn!("Flaky candidate {}: {:?}", target, outcomes);
+                self.flaky.borrow_mut().push(Flaky {
+                    target: target.to_string(),
+                    outcomes,
+                });
+                execution.outcome = TestOutcome::Unresolved("Flaky".to_string());
+                return execution;
+            }
+        }
+        execution
+    }
+
+    /// Executes the command for the `target` and collects its exit status and output to explain
+    /// the outcome. The unresolved outcome is retried as in `run`, but the interesting one is not
+    /// confirmed. Returns `Error::OracleFailed` if the command cannot be executed.
+    pub fn probe(&self, target: &str) -> Result<Probe, Error> {
+        let mut retries = 0;
+        loop {
+            let probe = self.probe_once(target)?;
+            if let Some(code) = probe.status.as_ref().and_then(|s| s.code()) {
+                if NOT_EXECUTED_CODES.contains(&code) && !self.exit_codes.contains(&code) {
+                    return Err(Error::OracleFailed(format!(
+                        "{}\n{}",
+                        self.command[0], probe
+                    )));
+                }
+            }
+            if !matches!(probe.outcome, TestOutcome::Unresolved(_)) || retries >= self.retries {
+                return Ok(probe);
+            }
+            retries += 1;
+        }
+    }
+
+    fn probe_once(&self, target: &str) -> Result<Probe, Error> {
+        let started = Instant::now();
+        let mut child = self
+            .spawn(target)
+            .map_err(|err| Error::OracleFailed(format!("{}: {}", self.command[0], err)))?;
+        let status = loop {
+            if interrupt::is_interrupted() {
+                let _ = kill(&mut child);
+                return Err(Error::Interrupted);
+            }
+            match child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if self.timed_out(started) => {
+                    kill(&mut child).map_err(|err| Error::new(err.to_string()))?;
+                    break None;
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(err) => return Err(Error::new(err.to_string())),
+            }
+        };
+        Ok(Probe {
+            outcome: match status {
+                Some(status) => self.outcome(target, status),
+                None => TestOutcome::Timeout,
+            },
+            status,
+            stdout: read_output(target, "stdout"),
+            stderr: read_output(target, "stderr"),
+        })
+    }
+
+    /// Executes the command for the `target`.
+    pub fn run(&self, target: &str) -> Execution {
+        match self.run_all(&[target]).pop() {
+            Some(Some(result)) => result,
+            _ => Execution::not_executed(),
+        }
+    }
+
+    /// Executes the command for each of the `targets` concurrently. Returns outcomes of the
+    /// executions in the same order. When some target reproduces the failure, the executions
+    /// started for the next targets are killed, and their outcomes are `None`. The retry policy
+    /// is applied to the outcomes up to the first interesting one. If the interesting outcome
+    /// turns out to be flaky, the killed executions are started again, so the result is the same
+    /// as if the targets were checked one by one.
+    pub fn run_all(&self, targets: &[&str]) -> Vec<Option<Execution>> {
+        let mut results = self.execute(targets);
+        let mut i = 0;
+        while i < results.len() {
+            let execution = match results[i].take() {
+                Some(execution) => execution,
+                None if interrupt::is_interrupted() => break,
+                None => {
+                    // The execution was killed because of the previous outcome that is not
+                    // confirmed.
+                    let rest = self.execute(&targets[i..]);
+                    results.splice(i.., rest);
+                    continue;
+                }
+            };
+            let execution = self.settle(targets[i], execution);
+            let found = self.is_interesting(&execution.outcome);
+            results[i] = Some(execution);
+            i += 1;
+            if found {
+                break;
+            }
+        }
+        // The outcomes after the interesting one are not confirmed, so they are dropped as the
+        // cancelled ones.
+        for result in results.iter_mut().skip(i) {
+            if matches!(result, Some(e) if self.is_interesting(&e.outcome)) {
+                *result = None;
+            }
+        }
+        results
+    }
+
+    /// Executes the command for each of the `targets` concurrently as described in `run_all`
+    /// without the retry policy. All the executions are killed if `dd` is interrupted.
+    fn execute(&self, targets: &[&str]) -> Vec<Option<Execution>> {
+        let started = Instant::now();
+        let mut results: Vec<Option<Execution>> = vec![None; targets.len()];
+        let mut children: Vec<Option<Child>> = vec![];
+        for (i, target) in targets.iter().enumerate() {
+            match self.spawn(target) {
+                Ok(child) => children.push(Some(child)),
+                Err(err) => {
+                    results[i] = Some(Execution {
+                        outcome: TestOutcome::Unresolved(err.to_string()),
+                        duration: Duration::ZERO,
+                    });
+                    children.push(None);
+                }
+            }
+        }
+
+        while children.iter().any(|c| c.is_some()) {
+            if interrupt::is_interrupted() {
+                for slot in children.iter_mut() {
+                    if let Some(mut child) = slot.take() {
+                        let _ = kill(&mut child);
+                    }
+                }
+                break;
+            }
+            for (i, slot) in children.iter_mut().enumerate() {
+                let child = match slot {
+                    Some(child) => child,
+                    None => continue,
+                };
+                let outcome = match child.try_wait() {
+                    Ok(None) if !self.timed_out(started) => continue,
+                    Ok(None) => match kill(child) {
+                        Ok(_) => TestOutcome::Timeout,
+                        Err(err) => TestOutcome::Unresolved(err.to_string()),
+                    },
+                    Ok(Some(status)) => self.outcome(targets[i], status),
+                    Err(err) => TestOutcome::Unresolved(err.to_string()),
+                };
+                results[i] = Some(Execution {
+                    outcome,
+                    duration: started.elapsed(),
+                });
+                *slot = None;
+            }
+            // The executions for the targets after the failing one cannot change the result.
+            let interesting =
+                |r: &Option<Execution>| matches!(r, Some(e) if self.is_interesting(&e.outcome));
+            if let Some(first) = results.iter().position(interesting) {
+                for slot in children.iter_mut().skip(first + 1) {
+                    if let Some(mut child) = slot.take() {
+                        let _ = kill(&mut child);
+                    }
+                }
+            }
+            if children.iter().any(|c| c.is_some()) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        results
+    }
+}
@@ -0,0 +1,158 @@
+//! Conditionals pass simplifies the `if` statements, so the conditional logic that doesn't matter
+//! for the failure collapses to straight-line code.
+//!
+//! The pass applies the transformations in stages from the most to the least aggressive one:
+//! 1. Replace the statement with the block of one of its branches. The first branches are tried
+//!    first.
+//! 2. Remove the `elseif` and `else` clauses.
+//! 3. Replace the conditions with `true`, then the remaining ones with `false`. Only the conditions
+//!    longer than the literal and the single variables are replaced, so the program grows by a few
+//!    bytes at most and loses the references to the variables.
+//!
+//! On each stage the transformations of all the statements are reduced at once using the delta
+//! debugging algorithm, then the program is parsed again for the next stage.
+use super::Pass;
+use crate::app::App;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::{Edit, Lua};
+use std::rc::Rc;
+use tree_sitter::Node as TSNode;
+
+/// Transformation of a conditional statement applied on a stage of the pass.
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    /// Replace the statement with the block of the branch with the given index.
+    Hoist(usize),
+    RemoveClauses,
+    /// Replace the conditions with the given literal.
+    Condition(&'static str),
+}
+
+pub struct PassConditionals<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+}
+
+/// Returns the edits that apply the `stage` to the `conditional` statement.
+fn stage_edits(
+    language: &dyn treesitter::Parser,
+    conditional: TSNode<'_>,
+    stage: Stage,
+) -> Vec<Edit> {
+    let branches = language.branches(conditional);
+    let text = |node: &TSNode| language.source()[node.byte_range()].to_string();
+    match stage {
+        Stage::Hoist(i) => branches
+            .get(i)
+            .map(|b| Edit::replace(&conditional, b.body.as_ref().map_or(String::new(), text)))
+            .into_iter()
+            .collect(),
+        Stage::RemoveClauses => branches
+            .iter()
+            .filter_map(|b| b.clause.as_ref().map(Edit::remove))
+            .collect(),
+        Stage::Condition(literal) => branches
+            .iter()
+            .filter_map(|b| b.condition)
+            // The replacement must make the program shorter or remove a reference to a variable.
+            .filter(|c| c.kind() == "identifier" || text(c).trim().len() > literal.len())
+            .map(|c| Edit::replace(&c, literal))
+            .collect(),
+    }
+}
+
+impl<'app> PassConditionals<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassConditionals {
+            ctx,
+            source_code: None,
+            ts_language: None,
+        })
+    }
+
+    /// Parses the given source code and sets it as the current one.
+    fn set_source(&mut self, source_code: String) -> Result<(), Error> {
+        self.ts_language = Some(Rc::new(Lua::new(&source_code)?));
+        self.source_code = Some(source_code);
+        Ok(())
+    }
+
+    /// Applies the `stage` to the conditional statements of the program. Returns `None` if no
+    /// statement may be transformed.
+    fn reduce_stage(&self, stage: Stage) -> Result<Option<String>, Error> {
+        let language = self.language();
+        let conditionals =
+            language.get_matches(language.source(), language.conditionals_query(), None);
+        let edits = conditionals
+            .into_iter()
+            .flat_map(|c| stage_edits(language.as_ref(), c, stage))
+            .collect::<Vec<_>>();
+        if edits.is_empty() {
+            return Ok(None);
+        }
+        log::debug!("Trying {:?} with {} edits", stage, edits.len());
+        delta::reduce_edits(&edits, self).map(Some)
+    }
+
+    /// Applies the `stage` and parses the reduced program. Returns false if no statement may be
+    /// transformed.
+    fn apply_stage(&mut self, stage: Stage) -> Result<bool, Error> {
+        match self.reduce_stage(stage)? {
+            Some(source) => {
+                if source != self.source_code() {
+                    self.set_source(source)?;
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<'app> Pass<'app> for PassConditionals<'app> {
+    fn name(&self) -> String {
+        "Conditionals".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        let source_code = self.read_source(source_code)?;
+        self.set_source(source_code)?;
+        // The stages continue until no statement has a branch with the next index.
+        let mut branch = 0;
+        while self.apply_stage(Stage::Hoist(branch))? {
+            branch += 1;
+        }
+        self.apply_stage(Stage::RemoveClauses)?;
+        self.apply_stage(Stage::Condition("true"))?;
+        self.apply_stage(Stage::Condition("false"))?;
+        Ok(self.source_code())
+    }
+}
@@ -0,0 +1,153 @@
+//! Expressions pass replaces the expressions of the program with simpler ones: their operands,
+//! the arguments of the calls, the literals `nil`, `0`, `""`, `true` and `{}`, or the same
+//! expressions without the parentheses around them.
+//!
+//! The replacements of each expression are sorted by their length and tried smallest-first. The
+//! pass works in rounds: on the round `n` each expression may be replaced with its `n`-th
+//! replacement, and the replacements of all the expressions are reduced at once using the delta
+//! debugging algorithm. When the program changes, it is parsed again and the rounds start over
+//! from the smallest replacements, so each expression gets the smallest replacement that
+//! reproduces the failure.
+use super::Pass;
+use crate::app::App;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::{Edit, Lua};
+use std::rc::Rc;
+use tree_sitter::Node as TSNode;
+
+/// Literals that may replace any expression.
+const LITERALS: [&str; 5] = ["nil", "0", "\"\"", "true", "{}"];
+
+pub struct PassExpressions<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+}
+
+/// Returns the replacements of the `expression` shorter than it, smallest-first.
+fn replacements(language: &dyn treesitter::Parser, expression: TSNode<'_>) -> Vec<String> {
+    let text = |node: &TSNode| language.source()[node.byte_range()].trim().to_string();
+    let original = language.source()[language.expression_range(&expression)].trim();
+    let mut result: Vec<String> = vec![];
+    let operands = language.operands(expression);
+    // The expression itself is a replacement when it is in parentheses.
+    let candidates = std::iter::once(&expression)
+        .chain(operands.iter())
+        .map(text)
+        .chain(LITERALS.iter().map(|l| l.to_string()));
+    for r in candidates {
+        if r.len() < original.len() && !result.contains(&r) {
+            result.push(r);
+        }
+    }
+    // The sort is stable, so the operands go before the literals of the same length.
+    result.sort_by_key(|r| r.len());
+    result
+}
+
+/// Collects the expressions nested into the `node`.
+fn collect_expressions<'a>(
+    language: &'a dyn treesitter::Parser,
+    node: TSNode<'a>,
+    acc: &mut Vec<TSNode<'a>>,
+) {
+    if language.is_expression(&node) {
+        acc.push(node);
+    }
+    for child in language.children(node) {
+        collect_expressions(language, child, acc);
+    }
+}
+
+impl<'app> PassExpressions<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassExpressions {
+            ctx,
+            source_code: None,
+            ts_language: None,
+        })
+    }
+
+    /// Parses the given source code and sets it as the current one.
+    fn set_source(&mut self, source_code: String) -> Result<(), Error> {
+        self.ts_language = Some(Rc::new(Lua::new(&source_code)?));
+        self.source_code = Some(source_code);
+        Ok(())
+    }
+
+    /// Replaces the expressions with their `n`-th replacements. Returns `None` if no expression
+    /// has so many replacements.
+    fn reduce_round(&self, n: usize) -> Result<Option<String>, Error> {
+        let language = self.language();
+        let mut expressions = vec![];
+        collect_expressions(language.as_ref(), language.ast_root(), &mut expressions);
+        let edits = expressions
+            .into_iter()
+            .filter_map(|e| {
+                let replacement = replacements(language.as_ref(), e).into_iter().nth(n)?;
+                let range = language.expression_range(&e);
+                Some(Edit {
+                    start_byte: range.start,
+                    end_byte: range.end,
+                    text: replacement,
+                    kind: e.kind().to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        if edits.is_empty() {
+            return Ok(None);
+        }
+        log::debug!("Trying replacements #{} of {} expressions", n, edits.len());
+        delta::reduce_edits(&edits, self).map(Some)
+    }
+}
+
+impl<'app> Pass<'app> for PassExpressions<'app> {
+    fn name(&self) -> String {
+        "Expressions".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        let source_code = self.read_source(source_code)?;
+        self.set_source(source_code)?;
+        // Each replacement makes the program shorter, so the rounds restart from the smallest
+        // replacements of the reparsed expressions until none of them changes it.
+        let mut n = 0;
+        while let Some(source) = self.reduce_round(n)? {
+            if source != self.source_code() {
+                self.set_source(source)?;
+                n = 0;
+            } else {
+                n += 1;
+            }
+        }
+        Ok(self.source_code())
+    }
+}
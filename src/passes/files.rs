@@ -0,0 +1,89 @@
+//! Files pass removes whole Lua files from the project in the recursive mode.
+use super::{Candidate, Pass};
+use crate::app::App;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::Lua;
+use std::rc::Rc;
+
+pub struct PassFiles<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+}
+
+impl<'app> PassFiles<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassFiles {
+            ctx,
+            source_code: None,
+            ts_language: None,
+        })
+    }
+}
+
+impl<'app> Pass<'app> for PassFiles<'app> {
+    fn name(&self) -> String {
+        "Files".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn rerun_after(&self, pass: &str) -> bool {
+        // The files become unused only when the code that requires them is removed.
+        matches!(pass, "imports" | "top" | "statements" | "functions" | "hdd")
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        self.source_code = Some(self.read_source(source_code)?);
+        self.ts_language = Some(Rc::new(Lua::new(self.source_code())?));
+        // The reduced file is the entry point of the program, so it is never removed.
+        let files = {
+            let workspace = self.ctx.workspace();
+            workspace
+                .lua_files()
+                .into_iter()
+                .filter(|f| f != workspace.target())
+                .collect::<Vec<String>>()
+        };
+        log::debug!("Bisecting {} files", files.len());
+        let source = self.source_code();
+        let (kept, source) = delta::ddmin_with(&files, self, |removed| {
+            Ok(Candidate {
+                removed_files: removed.to_vec(),
+                ..Candidate::new(source.clone())
+            })
+        })?;
+        let removed = files
+            .into_iter()
+            .filter(|f| !kept.contains(f))
+            .collect::<Vec<String>>();
+        log::debug!("Removed files: {:?}", removed);
+        self.ctx.workspace_mut().remove_files(&removed);
+        Ok(source)
+    }
+}
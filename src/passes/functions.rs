@@ -0,0 +1,135 @@
+//! Functions pass simplifies the functions of the program: the declared ones, including local
+//! functions and methods, and the anonymous ones passed as arguments.
+//!
+//! The pass applies the transformations in stages from the most to the least aggressive one:
+//! 1. Delete the functions. The declarations are removed, and the anonymous functions are
+//!    replaced with `nil`.
+//! 2. Remove the bodies of the remaining functions.
+//! 3. Replace the bodies of the remaining functions with `return nil`.
+//!
+//! On each stage the transformations of all the functions are reduced at once using the delta
+//! debugging algorithm, then the program is parsed again for the next stage.
+use super::Pass;
+use crate::app::App;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::{Edit, Lua};
+use std::rc::Rc;
+use tree_sitter::Node as TSNode;
+
+/// Body of the function that returns nothing, but keeps the function returning a value.
+const RETURN_NIL: &str = "return nil";
+
+/// Transformation of a function applied on a stage of the pass.
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Delete,
+    Empty,
+    ReturnNil,
+}
+
+const STAGES: [Stage; 3] = [Stage::Delete, Stage::Empty, Stage::ReturnNil];
+
+pub struct PassFunctions<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+}
+
+/// Returns the edit that applies the `stage` to the `function`, or `None` if it doesn't change it.
+fn stage_edit(
+    language: &dyn treesitter::Parser,
+    function: TSNode<'_>,
+    stage: Stage,
+) -> Option<Edit> {
+    let body = language.function_body(function);
+    match stage {
+        // The declarations are statements of the enclosing block, the rest are expressions.
+        Stage::Delete => match function.parent() {
+            Some(parent) if language.is_statement_block(&parent) => {
+                Some(language.removal_edit(&function))
+            }
+            _ => Some(Edit::replace(&function, "nil")),
+        },
+        Stage::Empty => body.map(|b| Edit::remove(&b)),
+        Stage::ReturnNil => body
+            .filter(|b| language.source()[b.byte_range()].trim() != RETURN_NIL)
+            .map(|b| Edit::replace(&b, RETURN_NIL)),
+    }
+}
+
+impl<'app> PassFunctions<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassFunctions {
+            ctx,
+            source_code: None,
+            ts_language: None,
+        })
+    }
+
+    /// Parses the given source code and sets it as the current one.
+    fn set_source(&mut self, source_code: String) -> Result<(), Error> {
+        self.ts_language = Some(Rc::new(Lua::new(&source_code)?));
+        self.source_code = Some(source_code);
+        Ok(())
+    }
+
+    /// Applies the `stage` to the functions of the program. Returns the reduced source code.
+    fn reduce_stage(&self, stage: Stage) -> Result<String, Error> {
+        let language = self.language();
+        let functions = language.get_matches(language.source(), language.functions_query(), None);
+        let edits = functions
+            .into_iter()
+            .filter_map(|f| stage_edit(language.as_ref(), f, stage))
+            .collect::<Vec<_>>();
+        if edits.is_empty() {
+            return Ok(self.source_code());
+        }
+        log::debug!("Trying {:?} for {} functions", stage, edits.len());
+        delta::reduce_edits(&edits, self)
+    }
+}
+
+impl<'app> Pass<'app> for PassFunctions<'app> {
+    fn name(&self) -> String {
+        "Functions".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        let source_code = self.read_source(source_code)?;
+        self.set_source(source_code)?;
+        for stage in STAGES {
+            let source = self.reduce_stage(stage)?;
+            if source != self.source_code() {
+                self.set_source(source)?;
+            }
+        }
+        Ok(self.source_code())
+    }
+}
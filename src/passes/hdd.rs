@@ -0,0 +1,145 @@
+//! HDD pass implements the Hierarchical Delta Debugging algorithm described in
+//! [Misherghi and Su, 2006](https://doi.org/10.1145/1134285.1134307).
+//!
+//! The pass walks the syntax tree level by level. A level contains the removable children of the
+//! block-like nodes (blocks, function bodies, tables, argument lists) nested into the given
+//! number of other block-like nodes. The children of each level are reduced at once using the
+//! delta debugging algorithm, then the program is parsed again and the pass goes to the next
+//! level. The walk is repeated until the program is not reduced anymore. The number of the
+//! reduced levels may be limited with the `max-depth` option.
+use super::Pass;
+use crate::app::App;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::{Edit, Lua};
+use std::rc::Rc;
+use tree_sitter::Node as TSNode;
+
+pub struct PassHdd<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+    /// Number of the levels reduced by the pass. All the levels are reduced if it is not set.
+    max_depth: Option<usize>,
+}
+
+/// Collects edits that remove the children of the block-like nodes located at the given `level`.
+fn collect_level(
+    language: &dyn treesitter::Parser,
+    node: TSNode<'_>,
+    node_level: usize,
+    level: usize,
+    acc: &mut Vec<Edit>,
+) {
+    let is_block = language.is_block(&node);
+    if is_block && node_level == level {
+        acc.extend(
+            language
+                .removable_children(node)
+                .iter()
+                .map(|c| language.removal_edit(c)),
+        );
+        return;
+    }
+    let child_level = if is_block { node_level + 1 } else { node_level };
+    if child_level > level {
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_level(language, child, child_level, level, acc);
+    }
+}
+
+impl<'app> PassHdd<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassHdd {
+            ctx,
+            source_code: None,
+            ts_language: None,
+            max_depth: None,
+        })
+    }
+
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Parses the given source code and sets it as the current one.
+    fn set_source(&mut self, source_code: String) -> Result<(), Error> {
+        self.ts_language = Some(Rc::new(Lua::new(&source_code)?));
+        self.source_code = Some(source_code);
+        Ok(())
+    }
+
+    /// Reduces the nodes of the given level. Returns `None` if the level doesn't exist.
+    fn reduce_level(&mut self, level: usize) -> Result<Option<String>, Error> {
+        let language = self.language();
+        let mut edits = vec![];
+        collect_level(language.as_ref(), language.ast_root(), 0, level, &mut edits);
+        if edits.is_empty() {
+            return Ok(None);
+        }
+        log::debug!("Bisecting {} nodes at level {}", edits.len(), level);
+        let (_, source) = delta::ddmin_edits(&edits, self)?;
+        Ok(Some(source))
+    }
+}
+
+impl<'app> Pass<'app> for PassHdd<'app> {
+    fn name(&self) -> String {
+        "HDD".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        let source_code = self.read_source(source_code)?;
+        self.set_source(source_code)?;
+        loop {
+            let round_start = self.source_code();
+            let mut level = 0;
+            while !matches!(self.max_depth, Some(max) if level >= max) {
+                match self.reduce_level(level)? {
+                    Some(reduced_source) => self.set_source(reduced_source)?,
+                    None => break,
+                }
+                level += 1;
+            }
+            if self.source_code() == round_start {
+                break;
+            }
+            log::debug!(
+                "HDD round reduced the program from {} to {} bytes",
+                round_start.len(),
+                self.source_code().len()
+            );
+        }
+        Ok(self.source_code())
+    }
+}
@@ -0,0 +1,165 @@
+//! Loops pass simplifies the `for`, `while` and `repeat` loops.
+//!
+//! The pass applies the transformations in stages from the most to the least aggressive one:
+//! 1. Delete the loops.
+//! 2. Replace the loops with their bodies executed once. The numeric `for` loop becomes a binding
+//!    of its variable to the start value, e.g. `local i = 1`, followed by the body. The generic
+//!    `for` loop over `pairs(t)` or `ipairs(t)` becomes a binding of its variables to `next(t)`,
+//!    e.g. `local k, v = next(t)`, followed by the body. Other generic loops are not unwrapped.
+//!    The bodies of the `while` and `repeat` loops are kept in a `do ... end` block, so their
+//!    local variables stay in their scope.
+//! 3. Replace the bounds of the numeric `for` loops with `1, 1`.
+//!
+//! On each stage the transformations of all the loops are reduced at once using the delta
+//! debugging algorithm, then the program is parsed again for the next stage.
+use super::Pass;
+use crate::app::App;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::{Edit, Lua};
+use regex::Regex;
+use std::rc::Rc;
+use tree_sitter::Node as TSNode;
+
+/// Bounds of the numeric `for` loop executed once.
+const SINGLE_ITERATION: &str = "1, 1";
+
+/// Transformation of a loop applied on a stage of the pass.
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Delete,
+    Unwrap,
+    SingleIteration,
+}
+
+const STAGES: [Stage; 3] = [Stage::Delete, Stage::Unwrap, Stage::SingleIteration];
+
+pub struct PassLoops<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+    /// Matches the iteration over a table with `pairs` or `ipairs`. The arguments are captured.
+    pairs_regex: Regex,
+}
+
+impl<'app> PassLoops<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassLoops {
+            ctx,
+            source_code: None,
+            ts_language: None,
+            pairs_regex: Regex::new(r"^i?pairs\s*(\(.*\))$").unwrap(),
+        })
+    }
+
+    /// Parses the given source code and sets it as the current one.
+    fn set_source(&mut self, source_code: String) -> Result<(), Error> {
+        self.ts_language = Some(Rc::new(Lua::new(&source_code)?));
+        self.source_code = Some(source_code);
+        Ok(())
+    }
+
+    /// Returns the edit that applies the `stage` to the `node` of the loop, or `None` if it
+    /// doesn't change it.
+    fn stage_edit(
+        &self,
+        language: &dyn treesitter::Parser,
+        node: TSNode<'_>,
+        stage: Stage,
+    ) -> Option<Edit> {
+        let parts = language.loop_parts(node);
+        let text = |node: &TSNode| &language.source()[node.byte_range()];
+        let body = parts.body.as_ref().map_or("", text);
+        match stage {
+            Stage::Delete => Some(language.removal_edit(&node)),
+            Stage::Unwrap => match parts.generic {
+                Some((names, values)) => {
+                    let values = text(&values).trim();
+                    let args = self.pairs_regex.captures(values)?;
+                    Some(Edit::replace(
+                        &node,
+                        format!("local {} = next{}\n{}", text(&names), &args[1], body),
+                    ))
+                }
+                None => match (parts.variable, parts.bounds) {
+                    (Some(variable), Some((start, _))) => Some(Edit::replace(
+                        &node,
+                        format!("local {} = {}\n{}", text(&variable), text(&start), body),
+                    )),
+                    _ => Some(Edit::replace(&node, format!("do\n{}\nend", body))),
+                },
+            },
+            Stage::SingleIteration => {
+                let (first, last) = parts.bounds?;
+                if language.source()[first.start_byte()..last.end_byte()] == *SINGLE_ITERATION {
+                    return None;
+                }
+                Some(Edit {
+                    start_byte: first.start_byte(),
+                    end_byte: last.end_byte(),
+                    text: SINGLE_ITERATION.to_string(),
+                    kind: first.parent().map_or("text", |p| p.kind()).to_string(),
+                })
+            }
+        }
+    }
+
+    /// Applies the `stage` to the loops of the program. Returns the reduced source code.
+    fn reduce_stage(&self, stage: Stage) -> Result<String, Error> {
+        let language = self.language();
+        let loops = language.get_matches(language.source(), language.loops_query(), None);
+        let edits = loops
+            .into_iter()
+            .filter_map(|l| self.stage_edit(language.as_ref(), l, stage))
+            .collect::<Vec<_>>();
+        if edits.is_empty() {
+            return Ok(self.source_code());
+        }
+        log::debug!("Trying {:?} for {} loops", stage, edits.len());
+        delta::reduce_edits(&edits, self)
+    }
+}
+
+impl<'app> Pass<'app> for PassLoops<'app> {
+    fn name(&self) -> String {
+        "Loops".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        let source_code = self.read_source(source_code)?;
+        self.set_source(source_code)?;
+        for stage in STAGES {
+            let source = self.reduce_stage(stage)?;
+            if source != self.source_code() {
+                self.set_source(source)?;
+            }
+        }
+        Ok(self.source_code())
+    }
+}
@@ -1,20 +1,30 @@
+pub mod conditionals;
+pub mod expressions;
+pub mod files;
+pub mod functions;
+pub mod hdd;
 pub mod imports;
+pub mod loops;
+pub mod statements;
 pub mod top;
 
-use super::app::App;
+use super::app::{App, PassConfig};
+use super::context::Context;
 use super::error::Error;
+use crate::interrupt;
+use crate::oracle::Execution;
 use crate::treesitter;
+use crate::treesitter::Edit;
+use std::cell::RefCell;
 use std::fmt;
-use std::process::{Child, Command, ExitStatus};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use tree_sitter::Node as TSNode;
-use wait_timeout::ChildExt;
 
 /// Outcomes for test frameworks defined at POSIX 1003.3.
 /// See: POSIX 1003.3: 1.4 A POSIX compliant test framework.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TestOutcome {
     /// Test succeeds
     Pass,
@@ -22,6 +32,8 @@ pub enum TestOutcome {
     Fail,
     /// Test produced intermediate results
     Unresolved(String),
+    /// Test exceeded the timeout
+    Timeout,
 }
 
 impl fmt::Display for TestOutcome {
@@ -30,6 +42,202 @@ impl fmt::Display for TestOutcome {
     }
 }
 
+/// A candidate program checked by the script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// Source code of the reduced file.
+    pub source: String,
+    /// Files removed from the project in the recursive mode.
+    pub removed_files: Vec<String>,
+    /// Edits of the source code parsed by the pass that produce the `source`. They are empty if
+    /// the candidate is not produced by edits.
+    pub edits: Vec<Edit>,
+    /// Hash of the source code to which the `edits` are applied.
+    pub base: u64,
+}
+
+impl Candidate {
+    pub fn new<S>(source: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Candidate {
+            source: source.into(),
+            removed_files: vec![],
+            edits: vec![],
+            base: 0,
+        }
+    }
+
+    /// Creates the candidate produced by the `edits` of the source code with the `base` hash.
+    pub fn with_edits<S>(source: S, base: u64, edits: Vec<Edit>) -> Self
+    where
+        S: Into<String>,
+    {
+        Candidate {
+            edits,
+            base,
+            ..Candidate::new(source)
+        }
+    }
+}
+
+/// State of the candidate before the execution of the oracle.
+enum Prepared {
+    /// The outcome is already known.
+    Cached(TestOutcome),
+    /// The candidate should be checked. `target` is the path given to the script, and it is
+    /// `None` if the candidate is checked in-process.
+    Pending { key: u64, target: Option<String> },
+}
+
+/// Looks up the outcome of the `candidate` with the given `id` in the cache and writes it to
+/// `temp_file` if it is not found and the oracle is a script.
+fn prepare(
+    ctx: &Context,
+    id: usize,
+    temp_file: String,
+    candidate: &Candidate,
+) -> Result<Prepared, Error> {
+    let key = ctx.candidate_key(&candidate.source, &candidate.removed_files);
+    let cached = ctx.cache().get(key);
+    if let Some(result) = cached {
+        log::debug!("Cached result: {}", &result);
+        let execution = Execution {
+            outcome: result,
+            duration: Duration::ZERO,
+        };
+        ctx.candidate_tested(id, key, None, true, candidate, Some(&execution))?;
+        return Ok(Prepared::Cached(execution.outcome));
+    }
+    if !ctx.writes_output() {
+        return Ok(Prepared::Pending { key, target: None });
+    }
+    let target = ctx
+        .workspace()
+        .write(&temp_file, &candidate.source, &candidate.removed_files)?;
+    Ok(Prepared::Pending {
+        key,
+        target: Some(target),
+    })
+}
+
+/// An instance of the pass in the pipeline.
+pub type PassInst<'a> = Rc<RefCell<dyn Pass<'a> + 'a>>;
+
+/// Option accepted by the pass, e.g. `max-depth` in `hdd:max-depth=3`.
+pub struct PassOption {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Description of the pass that may be added to the pipeline.
+pub struct PassInfo {
+    /// Name of the pass used in the command line.
+    pub name: &'static str,
+    pub description: &'static str,
+    pub options: &'static [PassOption],
+    /// Creates the pass with the options given in the `config`.
+    pub create: for<'a> fn(Rc<Context<'a>>, &PassConfig) -> Result<PassInst<'a>, Error>,
+}
+
+fn create_imports<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(imports::PassImports::new(ctx)?)))
+}
+
+fn create_top<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(top::PassTop::new(ctx)?)))
+}
+
+fn create_statements<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(statements::PassStatements::new(ctx)?)))
+}
+
+fn create_functions<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(functions::PassFunctions::new(ctx)?)))
+}
+
+fn create_conditionals<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(conditionals::PassConditionals::new(
+        ctx,
+    )?)))
+}
+
+fn create_loops<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(loops::PassLoops::new(ctx)?)))
+}
+
+fn create_expressions<'a>(ctx: Rc<Context<'a>>, _: &PassConfig) -> Result<PassInst<'a>, Error> {
+    Ok(Rc::new(RefCell::new(expressions::PassExpressions::new(
+        ctx,
+    )?)))
+}
+
+fn create_hdd<'a>(ctx: Rc<Context<'a>>, config: &PassConfig) -> Result<PassInst<'a>, Error> {
+    let pass = hdd::PassHdd::new(ctx)?.max_depth(config.option("max-depth")?);
+    Ok(Rc::new(RefCell::new(pass)))
+}
+
+/// Passes available in the pipeline.
+pub static REGISTRY: &[PassInfo] = &[
+    PassInfo {
+        name: "imports",
+        description: "Removes the imported modules",
+        options: &[],
+        create: create_imports,
+    },
+    PassInfo {
+        name: "top",
+        description: "Removes the top-level statements",
+        options: &[],
+        create: create_top,
+    },
+    PassInfo {
+        name: "statements",
+        description: "Removes the statements of each block starting from the innermost ones",
+        options: &[],
+        create: create_statements,
+    },
+    PassInfo {
+        name: "functions",
+        description: "Deletes the functions or empties their bodies",
+        options: &[],
+        create: create_functions,
+    },
+    PassInfo {
+        name: "conditionals",
+        description: "Replaces the if statements with their branches and simplifies conditions",
+        options: &[],
+        create: create_conditionals,
+    },
+    PassInfo {
+        name: "loops",
+        description: "Deletes the loops or replaces them with a single iteration",
+        options: &[],
+        create: create_loops,
+    },
+    PassInfo {
+        name: "expressions",
+        description: "Replaces the expressions with their operands or literals",
+        options: &[],
+        create: create_expressions,
+    },
+    PassInfo {
+        name: "hdd",
+        description: "Removes the nested statements, fields and arguments level by level",
+        options: &[PassOption {
+            name: "max-depth",
+            description: "Number of the nesting levels reduced by the pass",
+        }],
+        create: create_hdd,
+    },
+];
+
+/// Returns the registered pass with the given `name`.
+pub fn find_pass(name: &str) -> Option<&'static PassInfo> {
+    REGISTRY.iter().find(|info| info.name == name)
+}
+
 static COUNTER: AtomicUsize = AtomicUsize::new(1);
 fn get_id() -> usize {
     COUNTER.fetch_add(1, Ordering::Relaxed)
@@ -39,14 +247,18 @@ pub trait Pass<'a> {
     /// Returns name of this pass.
     fn name(&self) -> String;
 
-    /// Returns absolute path to the temporary file created for this pass.
-    fn next_temp_file(&self) -> String {
-        return format!("{}/{}", self.temp_dir(), get_id());
+    /// Returns absolute path to the temporary file created by this pass for the candidate with
+    /// the given `id`.
+    fn temp_file(&self, id: usize) -> String {
+        format!("{}/{}", self.temp_dir(), id)
     }
 
     /// Returns temporary directory used by this pass.
     fn temp_dir(&self) -> String;
 
+    /// Returns the context shared between the passes.
+    fn context(&self) -> &Context<'a>;
+
     /// Returns application configuration used by this pass.
     fn app(&self) -> &App;
 
@@ -56,79 +268,134 @@ pub trait Pass<'a> {
     /// Returns tree-sitter parser.
     fn language(&self) -> Rc<dyn treesitter::Parser>;
 
+    /// Returns true if the pass may reduce the program again after it has been changed by the
+    /// `pass` named as in the registry, e.g. `top`.
+    fn rerun_after(&self, _pass: &str) -> bool {
+        true
+    }
+
     /// Executes the pass. If no `source_code` is given, it will be read from the file specified in
     /// App configuration. Returns source code reduced by this pass on success.
     fn run(&mut self, source_code: Option<&str>) -> Result<String, Error>;
 
     /// Returns the result of the execution of the check script. The source code for test will be
-    /// generated from the given `source_code`, from which the `removed_nodes` are removed.
-    fn test_nodes(
-        &self,
-        source_code: &str,
-        removed_nodes: &[TSNode<'a>],
-    ) -> Result<(TestOutcome, String), Error> {
-        let source = self.language().remove_nodes(source_code, removed_nodes)?;
+    /// generated from the source code parsed by the `language`, from which the `removed_nodes` are
+    /// removed.
+    fn test_nodes(&self, removed_nodes: &[TSNode<'a>]) -> Result<(TestOutcome, String), Error> {
+        let source = self.language().remove_nodes(removed_nodes)?;
+        self.test_source(&source)
+    }
+
+    /// Returns the result of the execution of the check script. The source code for test will be
+    /// generated from the source code parsed by the `language` by applying the `edits`.
+    fn test_edits(&self, edits: &[Edit]) -> Result<(TestOutcome, String), Error> {
+        let source = treesitter::apply_edits(self.language().source(), edits)?;
         self.test_source(&source)
     }
 
     /// Returns the result of the execution of the check script for the source code.
     fn test_source(&self, source: &str) -> Result<(TestOutcome, String), Error> {
-        let temp_file = self.next_temp_file();
-        let temp_file = temp_file.as_str();
-        if std::fs::write(temp_file, source).is_err() {
-            return Err(Error::new("Cannot write to file"));
-        };
-        let result = run_command(
-            self.app().script.as_str(),
-            self.app().timeout,
-            vec![temp_file],
-        );
-        log::debug!("File: {} Result: {}", &temp_file, &result);
+        let result = self.test_candidate(&Candidate::new(source))?;
         Ok((result, source.to_string()))
     }
 
-    /// Reads source code from the argument or from the file specified in the App configuration.
-    fn read_source(&self, source: Option<&str>) -> Result<String, Error> {
-        match source {
-            Some(s) => Ok(s.to_string()),
-            None => std::fs::read_to_string(&self.app().file)
-                .map_err(|err| Error::new(format!("{}", err))),
-        }
+    /// Returns the result of the execution of the check script for the `candidate`.
+    fn test_candidate(&self, candidate: &Candidate) -> Result<TestOutcome, Error> {
+        let id = get_id();
+        let (key, target) = match prepare(self.context(), id, self.temp_file(id), candidate)? {
+            Prepared::Cached(result) => return Ok(result),
+            Prepared::Pending { key, target } => (key, target),
+        };
+        let execution = match self
+            .context()
+            .run_all(&[(target.as_deref(), candidate)])
+            .pop()
+        {
+            Some(Some(execution)) => execution,
+            _ => return Err(Error::new("The candidate has not been checked")),
+        };
+        interrupt::check()?;
+        log::debug!("Candidate: {} Result: {}", id, &execution.outcome);
+        self.context().candidate_tested(
+            id,
+            key,
+            target.as_deref(),
+            false,
+            candidate,
+            Some(&execution),
+        )?;
+        self.context().cache().insert(key, &execution.outcome)?;
+        Ok(execution.outcome)
     }
-}
 
-/// Wait wraps over `wait` function implementing timeout logic.
-fn wait(child: &mut Child, timeout: Option<u32>) -> Result<ExitStatus, std::io::Error> {
-    match timeout {
-        None => child.wait(),
-        Some(timeout) => {
-            let time_sec = Duration::from_secs(timeout as u64);
-            match child.wait_timeout(time_sec).unwrap() {
-                Some(s) => Ok(s),
+    /// Checks the `candidates` running the scripts for them concurrently. Returns index of the
+    /// first failing candidate. The scripts started for the next candidates are killed as soon as
+    /// some candidate fails, so the result is the same as in the sequential execution.
+    fn find_failing(&self, candidates: &[Candidate]) -> Result<Option<usize>, Error> {
+        if candidates.len() == 1 {
+            let result = self.test_candidate(&candidates[0])?;
+            return Ok(Some(0).filter(|_| self.context().is_interesting(&result)));
+        }
+        // Index of the first failing candidate found in the cache. All the candidates that are
+        // checked with the oracle precede it.
+        let mut first_cached_failing = None;
+        let mut pending = vec![];
+        for (i, candidate) in candidates.iter().enumerate() {
+            let id = get_id();
+            match prepare(self.context(), id, self.temp_file(id), candidate)? {
+                Prepared::Cached(result) if self.context().is_interesting(&result) => {
+                    first_cached_failing = Some(i);
+                    break;
+                }
+                Prepared::Cached(_) => (),
+                Prepared::Pending { key, target } => pending.push((i, id, key, target)),
+            }
+        }
+        let checked = pending
+            .iter()
+            .map(|(i, _, _, target)| (target.as_deref(), &candidates[*i]))
+            .collect::<Vec<_>>();
+        let mut first_failing = None;
+        let results = self.context().run_all(&checked);
+        for ((i, id, key, target), execution) in pending.iter().zip(results) {
+            self.context().candidate_tested(
+                *id,
+                *key,
+                target.as_deref(),
+                false,
+                &candidates[*i],
+                execution.as_ref(),
+            )?;
+            let result = match execution {
+                Some(execution) => execution.outcome,
                 None => {
-                    // Time out. Kill the child and return status code which will contain the
-                    // error.
-                    child.kill().unwrap();
-                    Ok(child.wait().unwrap())
+                    log::debug!("Candidate: {} Cancelled", id);
+                    continue;
                 }
+            };
+            log::debug!("Candidate: {} Result: {}", id, &result);
+            self.context().cache().insert(*key, &result)?;
+            if self.context().is_interesting(&result) && first_failing.is_none() {
+                first_failing = Some(*i);
             }
         }
+        interrupt::check()?;
+        Ok(first_failing.or(first_cached_failing))
     }
-}
 
-/// Executes the given shell command and returns TestOutcome::PASS if it returns 0 return code.
-fn run_command(script: &str, timeout: Option<u32>, args: Vec<&str>) -> TestOutcome {
-    match Command::new(script).args(&args).spawn() {
-        Ok(mut child) => match wait(&mut child, timeout) {
-            Ok(s) => {
-                if s.success() {
-                    TestOutcome::Pass
-                } else {
-                    TestOutcome::Fail
-                }
-            }
-            Err(err) => TestOutcome::Unresolved(err.to_string()),
-        },
-        Err(err) => TestOutcome::Unresolved(err.to_string()),
+    /// Saves the smallest failing `candidate` found so far to the output directory.
+    fn save_best(&self, candidate: &Candidate) -> Result<(), Error> {
+        self.context()
+            .save_best(&candidate.source, &candidate.removed_files)?;
+        self.context().candidate_accepted(candidate)
+    }
+
+    /// Reads source code from the argument or from the file specified in the App configuration.
+    fn read_source(&self, source: Option<&str>) -> Result<String, Error> {
+        match source {
+            Some(s) => Ok(s.to_string()),
+            None => std::fs::read_to_string(&self.app().file)
+                .map_err(|err| Error::new(format!("{}", err))),
+        }
     }
 }
@@ -0,0 +1,115 @@
+//! Statements pass removes the statements of every block in the program: the bodies of functions,
+//! loops and `do ... end` blocks, the branches of conditionals and the top level.
+//!
+//! The statements of each block are reduced separately using the delta debugging algorithm,
+//! starting from the innermost blocks, so the enclosing block is reduced when its nested blocks
+//! are already minimal. The program is parsed once: the edits that remove the statements of the
+//! reduced blocks are kept and applied together with the candidate edits of the next blocks.
+use super::{Candidate, Pass};
+use crate::app::App;
+use crate::cache::Hasher;
+use crate::context::Context;
+use crate::delta;
+use crate::error::Error;
+use crate::treesitter;
+use crate::treesitter::{apply_edits, Edit, Lua};
+use std::rc::Rc;
+use tree_sitter::Node as TSNode;
+
+pub struct PassStatements<'app> {
+    ctx: Rc<Context<'app>>,
+    source_code: Option<String>,
+    ts_language: Option<Rc<dyn treesitter::Parser>>,
+}
+
+/// Collects the blocks of statements nested into the `node` in post-order, so the inner blocks
+/// precede the outer ones.
+fn collect_blocks<'a>(
+    language: &'a dyn treesitter::Parser,
+    node: TSNode<'a>,
+    acc: &mut Vec<TSNode<'a>>,
+) {
+    for child in language.children(node) {
+        collect_blocks(language, child, acc);
+    }
+    if language.is_statement_block(&node) {
+        acc.push(node);
+    }
+}
+
+impl<'app> PassStatements<'app> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
+        Ok(PassStatements {
+            ctx,
+            source_code: None,
+            ts_language: None,
+        })
+    }
+}
+
+impl<'app> Pass<'app> for PassStatements<'app> {
+    fn name(&self) -> String {
+        "Statements".to_string()
+    }
+
+    fn temp_dir(&self) -> String {
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
+                .to_string_lossy(),
+        )
+    }
+
+    fn app(&self) -> &App {
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
+    }
+
+    fn source_code(&self) -> String {
+        self.source_code.as_ref().unwrap().clone()
+    }
+
+    fn language(&self) -> Rc<dyn treesitter::Parser> {
+        self.ts_language.as_ref().unwrap().clone()
+    }
+
+    fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
+        self.source_code = Some(self.read_source(source_code)?);
+        self.ts_language = Some(Rc::new(Lua::new(self.source_code())?));
+        let language = self.language();
+        let original = language.source();
+        let base = Hasher::hash_str(original);
+        let mut blocks = vec![];
+        collect_blocks(language.as_ref(), language.ast_root(), &mut blocks);
+        log::debug!("Reducing {} blocks", blocks.len());
+        // Edits that remove the statements from the blocks reduced so far.
+        let mut applied: Vec<Edit> = vec![];
+        for block in blocks.iter() {
+            let statements = language.removable_children(*block);
+            if statements.len() < 2 {
+                continue;
+            }
+            let edits = statements
+                .iter()
+                .map(|s| language.removal_edit(s))
+                .collect::<Vec<_>>();
+            log::debug!(
+                "Bisecting {} statements of {} at {}",
+                edits.len(),
+                block.kind(),
+                block.start_byte()
+            );
+            let (kept, source) = delta::ddmin_with(&edits, self, |removed| {
+                let edits = [&applied[..], removed].concat();
+                let source = apply_edits(original, &edits)?;
+                Ok(Candidate::with_edits(source, base, edits))
+            })?;
+            applied.extend(edits.into_iter().filter(|e| !kept.contains(e)));
+            self.source_code = Some(source);
+        }
+        Ok(self.source_code())
+    }
+}
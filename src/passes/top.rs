@@ -1,6 +1,7 @@
 //! Top pass removes top-level statements from the given program using delta-debugging tecnhique.
 use super::Pass;
 use crate::app::App;
+use crate::context::Context;
 use crate::delta;
 use crate::error::Error;
 use crate::treesitter;
@@ -8,15 +9,15 @@ use crate::treesitter::Lua;
 use std::rc::Rc;
 
 pub struct PassTop<'app> {
-    app: &'app App,
+    ctx: Rc<Context<'app>>,
     source_code: Option<String>,
     ts_language: Option<Rc<dyn treesitter::Parser>>,
 }
 
 impl<'app> PassTop<'app> {
-    pub fn from_app(app: &'app App) -> Result<Self, Error> {
+    pub fn new(ctx: Rc<Context<'app>>) -> Result<Self, Error> {
         Ok(PassTop {
-            app,
+            ctx,
             source_code: None,
             ts_language: None,
         })
@@ -29,15 +30,19 @@ impl<'app> Pass<'app> for PassTop<'app> {
     }
 
     fn temp_dir(&self) -> String {
-        return String::from(
-            std::path::PathBuf::from(&self.app.output_dir)
-                .join(&self.name())
+        String::from(
+            std::path::PathBuf::from(&self.app().output_dir)
+                .join(self.name())
                 .to_string_lossy(),
-        );
+        )
     }
 
     fn app(&self) -> &App {
-        self.app
+        self.ctx.app()
+    }
+
+    fn context(&self) -> &Context<'app> {
+        &self.ctx
     }
 
     fn source_code(&self) -> String {
@@ -50,7 +55,7 @@ impl<'app> Pass<'app> for PassTop<'app> {
 
     fn run(&mut self, source_code: Option<&str>) -> Result<String, Error> {
         self.source_code = Some(self.read_source(source_code)?);
-        self.ts_language = Some(Rc::new(Lua::new(&self.source_code())?));
+        self.ts_language = Some(Rc::new(Lua::new(self.source_code())?));
         let language = self.language();
         let ast_root = language.ast_root();
         let top_nodes = language.children(ast_root);
@@ -0,0 +1,416 @@
+//! Progress reports the state of the reduction on stderr: the executed pass, the granularity of
+//! the delta debugging, the number of the tested candidates and the size of the program. When
+//! stderr is a terminal, the progress line is redrawn in place, otherwise it is printed from time
+//! to time. The statistics of the passes are collected to show them at the end of the reduction.
+//! The library users receive the snapshots of the progress instead of the output.
+use crate::treesitter::count_statements;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Minimal interval between the progress lines printed when stderr is a terminal.
+const INTERACTIVE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Minimal interval between the progress lines printed when stderr is not a terminal.
+const BATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Size of the reduced program.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Size {
+    pub bytes: usize,
+    pub lines: usize,
+    /// Number of the statements in the Lua blocks. It is measured only when the passes start and
+    /// finish, because the source code must be parsed.
+    pub statements: usize,
+}
+
+impl Size {
+    /// Measures the source code of a Lua file.
+    pub fn of(source: &str) -> Self {
+        Size {
+            statements: count_statements(source),
+            ..Size::of_text(source)
+        }
+    }
+
+    /// Measures the source code without parsing it, so the number of statements is zero.
+    pub fn of_text(source: &str) -> Self {
+        Size {
+            bytes: source.len(),
+            lines: source.lines().count(),
+            statements: 0,
+        }
+    }
+}
+
+impl std::ops::Add for Size {
+    type Output = Size;
+
+    fn add(self, other: Size) -> Size {
+        Size {
+            bytes: self.bytes + other.bytes,
+            lines: self.lines + other.lines,
+            statements: self.statements + other.statements,
+        }
+    }
+}
+
+impl std::iter::Sum for Size {
+    fn sum<I: Iterator<Item = Size>>(iter: I) -> Size {
+        iter.fold(Size::default(), |acc, s| acc + s)
+    }
+}
+
+/// Formats the duration as `1h02m03s`, `2m03s` or `3.4s`.
+struct Elapsed(Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs();
+        if secs >= 3600 {
+            write!(
+                f,
+                "{}h{:02}m{:02}s",
+                secs / 3600,
+                secs % 3600 / 60,
+                secs % 60
+            )
+        } else if secs >= 60 {
+            write!(f, "{}m{:02}s", secs / 60, secs % 60)
+        } else {
+            write!(f, "{:.1}s", self.0.as_secs_f64())
+        }
+    }
+}
+
+/// Statistics of the runs of a pass.
+#[derive(Debug, Clone)]
+pub struct PassStats {
+    /// Configuration of the pass, e.g. `hdd:max-depth=3`.
+    pub name: String,
+    pub runs: usize,
+    /// Number of the candidates checked with the script.
+    pub tested: usize,
+    /// Number of the candidates whose outcomes were taken from the cache.
+    pub cached: usize,
+    /// Size of the code removed by the pass.
+    pub removed: Size,
+    pub elapsed: Duration,
+}
+
+/// State of the reduction given to the listener of the progress.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub round: usize,
+    /// Configuration of the running pass, e.g. `hdd:max-depth=3`.
+    pub pass: Option<String>,
+    pub initial: Size,
+    /// Size of the smallest failing program found so far.
+    pub current: Size,
+    /// Number of the candidates checked with the oracle.
+    pub tested: usize,
+    /// Number of the candidates whose outcomes were taken from the cache.
+    pub cached: usize,
+    pub elapsed: Duration,
+}
+
+/// State of the delta debugging executed by the running pass.
+#[derive(Debug, Clone, Copy)]
+struct DdminProgress {
+    granularity: usize,
+    /// Index of the next tested step.
+    position: usize,
+    steps: usize,
+}
+
+/// The pass executed at the moment.
+struct RunningPass {
+    name: String,
+    target: String,
+    started: Instant,
+    before: Size,
+    /// Cache counters when the pass started.
+    hits: usize,
+    misses: usize,
+    ddmin: Option<DdminProgress>,
+}
+
+pub struct Progress {
+    /// The progress is shown if the verbosity is greater than zero.
+    verbosity: u8,
+    /// True if stderr is a terminal.
+    interactive: bool,
+    started: Instant,
+    /// True if the reduction has started, so the initial size of the program is known.
+    running: bool,
+    last_shown: Option<Instant>,
+    /// True if the progress line is drawn and should be erased before other output.
+    drawn: bool,
+    initial: Size,
+    current: Size,
+    round: usize,
+    /// Cache counters updated by the passes.
+    hits: usize,
+    misses: usize,
+    pass: Option<RunningPass>,
+    stats: Vec<PassStats>,
+}
+
+#[cfg(unix)]
+fn stderr_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDERR_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stderr_is_terminal() -> bool {
+    false
+}
+
+impl Progress {
+    /// Creates the progress shown on stderr according to the `verbosity`. On the levels above
+    /// one the progress is interleaved with the log messages, so it is never redrawn in place.
+    pub fn new(verbosity: u8) -> Self {
+        Progress {
+            verbosity,
+            interactive: verbosity == 1 && stderr_is_terminal(),
+            started: Instant::now(),
+            running: false,
+            last_shown: None,
+            drawn: false,
+            initial: Size::default(),
+            current: Size::default(),
+            round: 0,
+            hits: 0,
+            misses: 0,
+            pass: None,
+            stats: vec![],
+        }
+    }
+
+    /// Starts measuring the reduction of the program of the `initial` size. The resumed reduction
+    /// continues from the program of the `current` size.
+    pub fn start(&mut self, initial: Size, current: Size) {
+        self.started = Instant::now();
+        self.running = true;
+        self.initial = initial;
+        self.current = current;
+    }
+
+    pub fn start_round(&mut self, round: usize) {
+        self.round = round;
+    }
+
+    /// Updates the cache counters.
+    pub fn set_cache(&mut self, hits: usize, misses: usize) {
+        self.hits = hits;
+        self.misses = misses;
+    }
+
+    /// Starts the pass `name` for the `target` file. The program has the given `size`.
+    pub fn start_pass(&mut self, name: &str, target: &str, size: Size) {
+        self.current = size;
+        self.pass = Some(RunningPass {
+            name: name.to_string(),
+            target: target.to_string(),
+            started: Instant::now(),
+            before: size,
+            hits: self.hits,
+            misses: self.misses,
+            ddmin: None,
+        });
+        self.show(true);
+    }
+
+    /// Updates the state of the delta debugging in the running pass.
+    pub fn ddmin(&mut self, granularity: usize, position: usize, steps: usize) {
+        if let Some(pass) = &mut self.pass {
+            pass.ddmin = Some(DdminProgress {
+                granularity,
+                position,
+                steps,
+            });
+        }
+        self.show(false);
+    }
+
+    /// Updates the size of the program when the pass finds a smaller failing candidate. The
+    /// number of the statements is kept until the pass finishes.
+    pub fn reduced(&mut self, size: Size) {
+        self.current = Size {
+            statements: self.current.statements,
+            ..size
+        };
+        if self.running {
+            self.show(false);
+        }
+    }
+
+    /// Finishes the running pass. The program has the given `size`.
+    pub fn finish_pass(&mut self, size: Size) {
+        self.current = size;
+        let pass = match self.pass.take() {
+            Some(pass) => pass,
+            None => return,
+        };
+        let removed = Size {
+            bytes: pass.before.bytes.saturating_sub(size.bytes),
+            lines: pass.before.lines.saturating_sub(size.lines),
+            statements: pass.before.statements.saturating_sub(size.statements),
+        };
+        let tested = self.misses - pass.misses;
+        let cached = self.hits - pass.hits;
+        let elapsed = pass.started.elapsed();
+        match self.stats.iter_mut().find(|s| s.name == pass.name) {
+            Some(stats) => {
+                stats.runs += 1;
+                stats.tested += tested;
+                stats.cached += cached;
+                stats.removed = stats.removed + removed;
+                stats.elapsed += elapsed;
+            }
+            None => self.stats.push(PassStats {
+                name: pass.name,
+                runs: 1,
+                tested,
+                cached,
+                removed,
+                elapsed,
+            }),
+        }
+    }
+
+    /// Returns the statistics of the passes in the order of their first runs.
+    pub fn stats(&self) -> &[PassStats] {
+        &self.stats
+    }
+
+    /// Returns the current state of the reduction.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            round: self.round,
+            pass: self.pass.as_ref().map(|p| p.name.clone()),
+            initial: self.initial,
+            current: self.current,
+            tested: self.misses,
+            cached: self.hits,
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    /// Returns the estimated time left until the delta debugging tries all the steps with the
+    /// current granularity, based on the average time of the script execution.
+    fn eta(&self, ddmin: &DdminProgress) -> Option<Duration> {
+        if self.misses == 0 {
+            return None;
+        }
+        let per_candidate = self.started.elapsed() / self.misses as u32;
+        Some(per_candidate * ddmin.steps.saturating_sub(ddmin.position) as u32)
+    }
+
+    /// Returns the progress line.
+    fn line(&self) -> String {
+        let mut line = format!("[{}] round {}", Elapsed(self.started.elapsed()), self.round);
+        if let Some(pass) = &self.pass {
+            line.push_str(&format!(" | {} for {}", pass.name, pass.target));
+            if let Some(ddmin) = &pass.ddmin {
+                line.push_str(&format!(
+                    " | granularity {}, step {}/{}",
+                    ddmin.granularity, ddmin.position, ddmin.steps
+                ));
+                if let Some(eta) = self.eta(ddmin) {
+                    line.push_str(&format!(", ETA {}", Elapsed(eta)));
+                }
+            }
+        }
+        line.push_str(&format!(
+            " | tested {}, cached {} | {} -> {} bytes, {} -> {} lines",
+            self.misses,
+            self.hits,
+            self.initial.bytes,
+            self.current.bytes,
+            self.initial.lines,
+            self.current.lines
+        ));
+        line
+    }
+
+    /// Prints the progress line unless it has been printed recently. The `force` line is always
+    /// printed.
+    fn show(&mut self, force: bool) {
+        if self.verbosity == 0 {
+            return;
+        }
+        let interval = if self.interactive {
+            INTERACTIVE_INTERVAL
+        } else {
+            BATCH_INTERVAL
+        };
+        if !force && matches!(self.last_shown, Some(t) if t.elapsed() < interval) {
+            return;
+        }
+        self.last_shown = Some(Instant::now());
+        let mut stderr = std::io::stderr();
+        if self.interactive {
+            let _ = write!(stderr, "\r\x1b[K{}", self.line());
+            self.drawn = true;
+        } else {
+            let _ = writeln!(stderr, "{}", self.line());
+        }
+        let _ = stderr.flush();
+    }
+
+    /// Erases the progress line drawn in the terminal.
+    pub fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[K");
+            self.drawn = false;
+        }
+    }
+
+    /// Prints the statistics of the passes to stderr.
+    pub fn finish(&mut self) {
+        self.clear();
+        if self.verbosity > 0 {
+            eprint!("{}", self);
+        }
+    }
+}
+
+/// Formats the statistics of the passes as a table.
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>5} {:>8} {:>8} {:>10} {:>8} {:>10} {:>10}",
+            "Pass", "Runs", "Tested", "Cached", "Bytes", "Lines", "Statements", "Time"
+        )?;
+        for s in self.stats.iter() {
+            writeln!(
+                f,
+                "{:<24} {:>5} {:>8} {:>8} {:>10} {:>8} {:>10} {:>10}",
+                s.name,
+                s.runs,
+                s.tested,
+                s.cached,
+                format!("-{}", s.removed.bytes),
+                format!("-{}", s.removed.lines),
+                format!("-{}", s.removed.statements),
+                Elapsed(s.elapsed).to_string()
+            )?;
+        }
+        writeln!(
+            f,
+            "Total: {} -> {} bytes, {} -> {} lines, {} -> {} statements, {} tested, {} cached in {}",
+            self.initial.bytes,
+            self.current.bytes,
+            self.initial.lines,
+            self.current.lines,
+            self.initial.statements,
+            self.current.statements,
+            self.misses,
+            self.hits,
+            Elapsed(self.started.elapsed())
+        )
+    }
+}
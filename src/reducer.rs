@@ -0,0 +1,172 @@
+//! Reducer is the library interface of `dd`. It reduces the source code of a Lua program checking
+//! the candidates with an in-process oracle, so nothing is executed or written to disk:
+//!
+//! ```no_run
+//! use dd::reducer::{Outcome, Reducer};
+//!
+//! let reduction = Reducer::new("local x = 1\nerror(x)\n", |candidate: &str| {
+//!     if candidate.contains("error") {
+//!         Outcome::Fail
+//!     } else {
+//!         Outcome::Pass
+//!     }
+//! })
+//! .passes("top,hdd")?
+//! .on_accepted(|accepted| eprintln!("{}: {} bytes", accepted.pass, accepted.source.len()))
+//! .run()?;
+//! println!("{}", reduction.source);
+//! # Ok::<(), dd::error::Error>(())
+//! ```
+use crate::app::{parse_passes, App};
+use crate::context::{AcceptedListener, Context, ProgressListener};
+use crate::driver;
+use crate::error::Error;
+pub use crate::oracle::Oracle;
+pub use crate::passes::TestOutcome as Outcome;
+use crate::progress::{PassStats, Size, Snapshot};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Name of the reduced file shown in the statistics of the passes.
+const FILE: &str = "input.lua";
+
+/// A candidate accepted as the smallest failing program found so far.
+#[derive(Debug)]
+pub struct Accepted<'a> {
+    /// Configuration of the pass that produced the candidate, e.g. `hdd:max-depth=3`.
+    pub pass: &'a str,
+    pub source: &'a str,
+}
+
+/// Result of the reduction.
+#[derive(Debug, Clone)]
+pub struct Reduction {
+    /// Source code of the reduced program.
+    pub source: String,
+    pub initial: Size,
+    pub size: Size,
+    /// Number of the completed rounds of the passes.
+    pub rounds: usize,
+    /// Number of the candidates checked with the oracle.
+    pub tested: usize,
+    /// Number of the candidates whose outcomes were taken from the cache.
+    pub cached: usize,
+    /// Statistics of the passes in the order of their first runs.
+    pub passes: Vec<PassStats>,
+    pub elapsed: Duration,
+}
+
+/// Builder of the reduction.
+pub struct Reducer<'r> {
+    app: App,
+    source: String,
+    oracle: Box<dyn Oracle + 'r>,
+    on_progress: Option<ProgressListener<'r>>,
+    on_accepted: Option<AcceptedListener<'r>>,
+}
+
+impl<'r> Reducer<'r> {
+    /// Creates the reduction of the `source` checked by the `oracle`. The candidate reproduces
+    /// the failure if the oracle returns `Outcome::Fail`.
+    pub fn new<S, O>(source: S, oracle: O) -> Self
+    where
+        S: Into<String>,
+        O: Oracle + 'r,
+    {
+        let mut app = App::new();
+        app.file = FILE.to_string();
+        app.verbosity = 0;
+        Reducer {
+            app,
+            source: source.into(),
+            oracle: Box::new(oracle),
+            on_progress: None,
+            on_accepted: None,
+        }
+    }
+
+    /// Sets the pipeline of the passes, e.g. `top,hdd:max-depth=3`.
+    pub fn passes(mut self, pipeline: &str) -> Result<Self, Error> {
+        self.app.passes = parse_passes(Some(pipeline))?;
+        Ok(self)
+    }
+
+    /// Enables the "reduce to subset" step of the delta debugging.
+    pub fn subsets(mut self, subsets: bool) -> Self {
+        self.app.subsets = subsets;
+        self
+    }
+
+    /// Limits the number of the rounds of the passes.
+    pub fn max_rounds(mut self, rounds: usize) -> Self {
+        self.app.max_rounds = Some(rounds);
+        self
+    }
+
+    /// Considers the `Outcome::Timeout` returned by the oracle as reproducing the failure.
+    pub fn timeout_interesting(mut self, interesting: bool) -> Self {
+        self.app.timeout_interesting = interesting;
+        self
+    }
+
+    /// Checks that an empty program doesn't reproduce the failure before the reduction.
+    pub fn check_empty(mut self, check: bool) -> Self {
+        self.app.check_empty = check;
+        self
+    }
+
+    /// Sets the `callback` called on each update of the progress.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Snapshot) + 'r,
+    {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets the `callback` called for each accepted candidate.
+    pub fn on_accepted<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Accepted) + 'r,
+    {
+        self.on_accepted = Some(Box::new(callback));
+        self
+    }
+
+    /// Reduces the program. Returns `Error::NotInteresting` if the original program doesn't
+    /// reproduce the failure.
+    pub fn run(self) -> Result<Reduction, Error> {
+        let Reducer {
+            app,
+            source,
+            oracle,
+            on_progress,
+            on_accepted,
+        } = self;
+        let ctx = Rc::new(Context::in_process(
+            &app,
+            &source,
+            oracle,
+            on_progress,
+            on_accepted,
+        ));
+        let passes = driver::create_passes(&ctx)?;
+        driver::start_progress(&ctx);
+        driver::check_input(&ctx)?;
+        driver::reduce(&ctx, &passes, None)?;
+        let source = ctx.workspace().target_source();
+        let progress = ctx.progress();
+        let snapshot = progress.snapshot();
+        let reduction = Reduction {
+            size: Size::of(&source),
+            source,
+            initial: snapshot.initial,
+            rounds: ctx.checkpoint().round,
+            tested: snapshot.tested,
+            cached: snapshot.cached,
+            passes: progress.stats().to_vec(),
+            elapsed: snapshot.elapsed,
+        };
+        Ok(reduction)
+    }
+}
@@ -0,0 +1,323 @@
+//! Trace records the transformations accepted during the reduction in the output directory, so
+//! the reduction may be reviewed and reproduced with `dd replay <trace>`.
+//!
+//! The trace is written in the JSON Lines format. The first line contains the configuration of
+//! the reduction and the original Lua files. Each next line is a step: the edits of the reduced
+//! file that produce the accepted candidate, the files removed from the project, the size of the
+//! reduced file and the outcome of the script. The ranges of the edits refer to the source code
+//! produced by the previous step, so the steps may be applied one by one to the original files.
+use crate::app::App;
+use crate::cache::Hasher;
+use crate::config::Config;
+use crate::error::Error;
+use crate::events::outcome_name;
+use crate::interrupt;
+use crate::oracle::ScriptOracle;
+use crate::passes::{Candidate, TestOutcome};
+use crate::treesitter::{apply_edits, diff_edit, merge_edits, rebase_edits, Edit};
+use crate::workspace::Workspace;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Directory next to the trace where the candidates are written during the replay.
+const REPLAY_DIR: &str = "replay";
+
+/// A change of the reduced file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEdit {
+    /// Kind of the changed node, or `text` if it is unknown.
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+    /// Replacement of the range. It is empty if the range is removed.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+}
+
+impl From<Edit> for TraceEdit {
+    fn from(edit: Edit) -> Self {
+        TraceEdit {
+            kind: edit.kind,
+            start: edit.start_byte,
+            end: edit.end_byte,
+            text: edit.text,
+        }
+    }
+}
+
+impl From<&TraceEdit> for Edit {
+    fn from(edit: &TraceEdit) -> Self {
+        Edit {
+            start_byte: edit.start,
+            end_byte: edit.end,
+            text: edit.text.clone(),
+            kind: edit.kind.clone(),
+        }
+    }
+}
+
+/// A transformation accepted during the reduction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// Number of the step starting from one.
+    pub step: usize,
+    /// Identifier of the candidate, the same as in the events and the temporary files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<usize>,
+    /// Configuration of the pass, e.g. `hdd:max-depth=3`.
+    pub pass: String,
+    pub target: String,
+    pub edits: Vec<TraceEdit>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_files: Vec<String>,
+    /// Size of the reduced file in bytes after the step.
+    pub size: usize,
+    pub outcome: String,
+}
+
+/// The first record of the trace.
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    config: Config,
+    /// Original source code of the Lua files.
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Header(Box<Header>),
+    Step(Step),
+}
+
+/// Writes the trace during the reduction.
+pub struct Trace {
+    path: String,
+    /// Header written to the new trace. It is `None` if the existing trace is continued.
+    header: Option<String>,
+    out: Option<fs::File>,
+    steps: usize,
+    /// Source code of the reduced file after the last step.
+    source: String,
+    /// Files removed from the project by the running pass.
+    removed: Vec<String>,
+    /// Base hash and edits of the last accepted candidate produced by edits.
+    last: Option<(u64, Vec<Edit>)>,
+}
+
+fn io_error(path: &str, err: impl std::fmt::Display) -> Error {
+    Error::new(format!("{}: {}", path, err))
+}
+
+impl Trace {
+    /// Creates the trace of the reduction configured in the `app` that starts from the `files`.
+    /// The resumed reduction continues the existing trace.
+    pub fn new(app: &App, files: &BTreeMap<String, String>) -> Result<Self, Error> {
+        let path = app.trace_file();
+        let (header, steps) = if app.resume {
+            let steps = fs::read_to_string(&path)
+                .map(|content| content.lines().count().saturating_sub(1))
+                .unwrap_or(0);
+            (None, steps)
+        } else {
+            let header = Record::Header(Box::new(Header {
+                config: Config::from_app(app),
+                files: files.clone(),
+            }));
+            let line = serde_json::to_string(&header).map_err(|err| io_error(&path, err))?;
+            (Some(line), 0)
+        };
+        Ok(Trace {
+            path,
+            header,
+            out: None,
+            steps,
+            source: String::new(),
+            removed: vec![],
+            last: None,
+        })
+    }
+
+    /// Starts the pass that reduces the `source` of the target file.
+    pub fn start_pass(&mut self, source: &str) {
+        self.source = source.to_string();
+        self.removed.clear();
+        self.last = None;
+    }
+
+    /// Returns the edits that change the source code after the last step to the `candidate`.
+    fn step_edits(&self, candidate: &Candidate) -> Vec<Edit> {
+        let edits = if candidate.edits.is_empty() {
+            None
+        } else {
+            match &self.last {
+                Some((base, old)) if *base == candidate.base => {
+                    rebase_edits(old, &candidate.edits).ok()
+                }
+                _ if Hasher::hash_str(&self.source) == candidate.base => {
+                    merge_edits(&candidate.edits).ok()
+                }
+                _ => None,
+            }
+        };
+        // The edits are checked, so the trace may be replayed even if the candidate is produced
+        // in another way.
+        match edits {
+            Some(edits) if apply_edits(&self.source, &edits).as_ref() == Ok(&candidate.source) => {
+                edits
+            }
+            _ if self.source == candidate.source => vec![],
+            _ => vec![diff_edit(&self.source, &candidate.source)],
+        }
+    }
+
+    /// Records the `candidate` accepted by the `pass` for the `target` file.
+    pub fn accept(
+        &mut self,
+        pass: &str,
+        target: &str,
+        id: Option<usize>,
+        candidate: &Candidate,
+        outcome: Option<&TestOutcome>,
+    ) -> Result<(), Error> {
+        let edits = self.step_edits(candidate);
+        let removed_files = candidate
+            .removed_files
+            .iter()
+            .filter(|f| !self.removed.contains(f))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.steps += 1;
+        let step = Step {
+            step: self.steps,
+            id,
+            pass: pass.to_string(),
+            target: target.to_string(),
+            edits: edits.into_iter().map(TraceEdit::from).collect(),
+            removed_files,
+            size: candidate.source.len(),
+            outcome: outcome
+                .map_or("unknown", |o| outcome_name(Some(o)).0)
+                .to_string(),
+        };
+        if !candidate.edits.is_empty() {
+            self.last = Some((candidate.base, candidate.edits.clone()));
+        }
+        self.source = candidate.source.clone();
+        self.removed = candidate.removed_files.clone();
+        self.write(&Record::Step(step))
+    }
+
+    /// Opens the trace file and writes the header to the new trace. It is called when the output
+    /// directory is prepared.
+    pub fn open(&mut self) -> Result<(), Error> {
+        if self.out.is_none() {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .map_err(|err| io_error(&self.path, err))?;
+            if let Some(header) = self.header.take() {
+                writeln!(file, "{}", header).map_err(|err| io_error(&self.path, err))?;
+            }
+            self.out = Some(file);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), Error> {
+        self.open()?;
+        let line = serde_json::to_string(record).map_err(|err| io_error(&self.path, err))?;
+        writeln!(self.out.as_mut().unwrap(), "{}", line).map_err(|err| io_error(&self.path, err))
+    }
+}
+
+/// Reads the trace at `path`. Returns its header and the steps.
+fn read_trace(path: &str) -> Result<(Header, Vec<Step>), Error> {
+    let content = fs::read_to_string(path).map_err(|err| io_error(path, err))?;
+    let mut header = None;
+    let mut steps = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let record: Record = serde_json::from_str(line)
+            .map_err(|err| io_error(&format!("{}:{}", path, i + 1), err))?;
+        match record {
+            Record::Header(h) if i == 0 => header = Some(*h),
+            Record::Step(step) if header.is_some() => steps.push(step),
+            _ => {
+                return Err(io_error(
+                    &format!("{}:{}", path, i + 1),
+                    "unexpected record",
+                ))
+            }
+        }
+    }
+    match header {
+        Some(header) => Ok((header, steps)),
+        None => Err(io_error(path, "the trace is empty")),
+    }
+}
+
+/// Applies the steps of the trace at `path` to the original files checking each of them with the
+/// script. The original files are taken from the trace, so they may be changed or deleted after
+/// the reduction. Returns the source code of the main file after the last step.
+pub fn replay(path: &str) -> Result<String, Error> {
+    let (header, steps) = read_trace(path)?;
+    let mut app = App::new();
+    header.config.apply(&mut app)?;
+    let dir = Path::new(path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(REPLAY_DIR);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|err| io_error(&dir.display().to_string(), err))?;
+    }
+    let mut workspace = Workspace::from_files(&app, header.files)?;
+    let oracle = ScriptOracle::from_app(&app)?;
+    for step in steps.iter() {
+        workspace.set_target(&step.target);
+        let edits = step.edits.iter().map(Edit::from).collect::<Vec<_>>();
+        let source = apply_edits(&workspace.target_source(), &edits)
+            .map_err(|err| Error::new(format!("Step {}: {}", step.step, err)))?;
+        if source.len() != step.size {
+            return Err(Error::new(format!(
+                "Step {}: the reduced file has {} bytes instead of {}",
+                step.step,
+                source.len(),
+                step.size
+            )));
+        }
+        workspace.remove_files(&step.removed_files);
+        let name = if workspace.is_project() {
+            step.step.to_string()
+        } else {
+            format!("{}.lua", step.step)
+        };
+        let target = workspace.write(&dir.join(name).display().to_string(), &source, &[])?;
+        let outcome = oracle.run(&target).outcome;
+        interrupt::check()?;
+        let kinds = edits.iter().map(|e| e.kind.as_str()).collect::<Vec<_>>();
+        eprintln!(
+            "Step {}: {} for {}: [{}], {} bytes: {}",
+            step.step,
+            step.pass,
+            step.target,
+            kinds.join(", "),
+            step.size,
+            outcome
+        );
+        if !oracle.is_interesting(&outcome) {
+            return Err(Error::new(format!(
+                "Step {} doesn't reproduce the failure: {}",
+                step.step, outcome
+            )));
+        }
+        workspace.update(&source);
+    }
+    Ok(workspace
+        .source(workspace.main())
+        .unwrap_or_default()
+        .to_string())
+}
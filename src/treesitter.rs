@@ -1,4 +1,3 @@
-use std::collections::{HashMap, HashSet};
 use tree_sitter::Language as TSLanguage;
 use tree_sitter::Node as TSNode;
 use tree_sitter::Parser as TSParser;
@@ -28,14 +27,100 @@ pub trait Parser {
     /// Returns a query that extracts imports for the given language.
     fn imports_query(&self) -> String;
 
+    /// Returns a query that extracts the functions: the declared ones, including local functions
+    /// and methods, and the anonymous ones.
+    fn functions_query(&self) -> String;
+
+    /// Returns the body of the function `node` captured by the `functions_query`. Returns `None`
+    /// if the body is empty.
+    fn function_body<'a>(&self, node: TSNode<'a>) -> Option<TSNode<'a>>;
+
+    /// Returns a query that extracts the conditional statements.
+    fn conditionals_query(&self) -> String;
+
+    /// Returns the branches of the conditional `node` captured by the `conditionals_query` in
+    /// the order of their clauses.
+    fn branches<'a>(&self, node: TSNode<'a>) -> Vec<Branch<'a>>;
+
+    /// Returns a query that extracts the `for`, `while` and `repeat` loops.
+    fn loops_query(&self) -> String;
+
+    /// Returns the parts of the loop `node` captured by the `loops_query`.
+    fn loop_parts<'a>(&self, node: TSNode<'a>) -> LoopParts<'a>;
+
+    /// Returns true if the `node` is an expression that may be replaced with another one, e.g. it
+    /// is not a call used as a statement or a target of an assignment.
+    fn is_expression(&self, node: &TSNode<'_>) -> bool;
+
+    /// Returns the subexpressions of the expression `node` that may replace it: the operands of
+    /// an operator or the arguments of a call.
+    fn operands<'a>(&self, node: TSNode<'a>) -> Vec<TSNode<'a>>;
+
+    /// Returns the byte range of the expression `node` including the parentheses around it, so
+    /// the expression may be replaced with its version without parentheses.
+    fn expression_range(&self, node: &TSNode<'_>) -> std::ops::Range<usize>;
+
+    /// Returns true if the children of the `node` may be removed independently of each other,
+    /// e.g. statements of a block or fields of a table.
+    fn is_block(&self, node: &TSNode<'_>) -> bool;
+
+    /// Returns true if the removable children of the `node` are statements, e.g. the body of a
+    /// function or a loop.
+    fn is_statement_block(&self, node: &TSNode<'_>) -> bool;
+
+    /// Returns true if the `node` separates elements of a list.
+    fn is_separator(&self, node: &TSNode<'_>) -> bool;
+
+    /// Returns children of the block-like `node` that may be removed. These are named children
+    /// that don't have a syntactic role in the parent node.
+    fn removable_children<'a>(&'a self, node: TSNode<'a>) -> Vec<TSNode<'a>> {
+        let mut cursor = node.walk();
+        let mut result = vec![];
+        if cursor.goto_first_child() {
+            loop {
+                let child = cursor.node();
+                if child.is_named() && cursor.field_name().is_none() {
+                    result.push(child);
+                }
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns an edit that removes the `node` together with the separator that follows it. If
+    /// the node is the last element of a list, the previous separator is removed.
+    fn removal_edit(&self, node: &TSNode<'_>) -> Edit {
+        let mut edit = Edit::remove(node);
+        match node.next_sibling() {
+            Some(next) if self.is_separator(&next) => edit.end_byte = next.end_byte(),
+            _ => {
+                if let Some(prev) = node.prev_sibling() {
+                    if self.is_separator(&prev) {
+                        edit.start_byte = prev.start_byte();
+                    }
+                }
+            }
+        }
+        edit
+    }
+
     /// Returns a tree-sitter node for the AST root.
     fn ast_root(&self) -> TSNode<'_> {
         self.tree().root_node()
     }
 
-    /// Removes `nodes` from the given `source_code`. Returns the source code after this
+    /// Returns the source code the tree was built from.
+    fn source(&self) -> &str;
+
+    /// Removes `nodes` of the tree from the source code. Returns the source code after this
     /// transformation.
-    fn remove_nodes<'a>(&self, source_code: &str, nodes: &[TSNode<'a>]) -> Result<String, String>;
+    fn remove_nodes<'a>(&self, nodes: &[TSNode<'a>]) -> Result<String, String> {
+        let edits = nodes.iter().map(Edit::remove).collect::<Vec<_>>();
+        apply_edits(self.source(), &edits)
+    }
 
     /// Performs a tree-sitter query for previously set source code and collects matched
     /// tree-sitter nodes.
@@ -44,7 +129,7 @@ pub trait Parser {
     /// * `source_code` - Source code of the program.
     /// * `query_text` - String representation of the tree-sitter query.
     /// * `filter` - A lambda function that filters collected captures. This is required to
-    ///              work around the bugs in some tree-sitter parsers.
+    ///   work around the bugs in some tree-sitter parsers.
     fn get_matches(
         &self,
         source_code: &str,
@@ -69,6 +154,180 @@ pub trait Parser {
     }
 }
 
+/// A branch of the conditional statement.
+#[derive(Debug, Clone, Copy)]
+pub struct Branch<'a> {
+    /// The `elseif` or `else` clause. It is `None` for the first branch.
+    pub clause: Option<TSNode<'a>>,
+    /// Condition of the branch. It is `None` for the `else` branch.
+    pub condition: Option<TSNode<'a>>,
+    /// Block executed in the branch. It is `None` if the branch is empty.
+    pub body: Option<TSNode<'a>>,
+}
+
+/// Parts of the loop statement.
+#[derive(Debug, Clone, Copy)]
+pub struct LoopParts<'a> {
+    /// Block executed in the loop. It is `None` if the loop is empty.
+    pub body: Option<TSNode<'a>>,
+    /// Variable of the numeric `for` loop.
+    pub variable: Option<TSNode<'a>>,
+    /// The first and the last of the start, end and step expressions of the numeric `for` loop.
+    pub bounds: Option<(TSNode<'a>, TSNode<'a>)>,
+    /// Variables and expressions of the generic `for` loop, e.g. `k, v` and `pairs(t)`.
+    pub generic: Option<(TSNode<'a>, TSNode<'a>)>,
+}
+
+/// A change of the source code: the bytes in range `start_byte..end_byte` are replaced with
+/// `text`. The range is taken from the nodes of the original tree, so it doesn't depend on the
+/// other changes made in the same source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edit {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub text: String,
+    /// Kind of the changed node.
+    pub kind: String,
+}
+
+impl Edit {
+    /// Creates an edit that removes the `node`.
+    pub fn remove(node: &TSNode<'_>) -> Edit {
+        Edit::replace(node, "")
+    }
+
+    /// Creates an edit that replaces the `node` with `text`.
+    pub fn replace<S>(node: &TSNode<'_>, text: S) -> Edit
+    where
+        S: Into<String>,
+    {
+        Edit {
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            text: text.into(),
+            kind: node.kind().to_string(),
+        }
+    }
+
+    /// Returns true if this edit changes the whole range of the `other` one.
+    fn covers(&self, other: &Edit) -> bool {
+        self.start_byte <= other.start_byte && other.end_byte <= self.end_byte
+    }
+}
+
+/// Sorts the `edits` and merges the overlapping ones. An edit that is nested into another one is
+/// dropped, because its range is already replaced by the outer edit. Partially overlapping edits
+/// are allowed only if both of them remove the text.
+pub fn merge_edits(edits: &[Edit]) -> Result<Vec<Edit>, String> {
+    let mut edits = edits.iter().collect::<Vec<&Edit>>();
+    // Outer edits go first when they start at the same position.
+    edits.sort_unstable_by_key(|e| (e.start_byte, std::cmp::Reverse(e.end_byte)));
+
+    let mut merged: Vec<Edit> = vec![];
+    for edit in edits.into_iter() {
+        if let Some(last) = merged.last_mut() {
+            if last.covers(edit) {
+                continue;
+            }
+            if edit.start_byte < last.end_byte {
+                if last.text.is_empty() && edit.text.is_empty() {
+                    last.end_byte = edit.end_byte;
+                    continue;
+                }
+                return Err(format!(
+                    "Overlapping edits: {}..{} and {}..{}",
+                    last.start_byte, last.end_byte, edit.start_byte, edit.end_byte
+                ));
+            }
+        }
+        merged.push(edit.clone());
+    }
+    Ok(merged)
+}
+
+/// Applies `edits` to the `source` code. The edits may be given in any order, and they are merged
+/// as described in `merge_edits`.
+pub fn apply_edits(source: &str, edits: &[Edit]) -> Result<String, String> {
+    let merged = merge_edits(edits)?;
+    let mut result = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in merged.iter() {
+        match source.get(pos..edit.start_byte) {
+            Some(s) => result.push_str(s),
+            None => return Err(format!("Invalid edit range: {}..{}", pos, edit.start_byte)),
+        }
+        result.push_str(&edit.text);
+        pos = edit.end_byte;
+    }
+    match source.get(pos..) {
+        Some(s) => result.push_str(s),
+        None => return Err(format!("Invalid edit range: {}..", pos)),
+    }
+    Ok(result)
+}
+
+/// Returns the edits that change the result of the `old` edits to the result of the `new` ones.
+/// Both are applied to the same source code, and the `new` edits include the `old` ones. The
+/// ranges of the returned edits refer to the source code changed by the `old` edits.
+pub fn rebase_edits(old: &[Edit], new: &[Edit]) -> Result<Vec<Edit>, String> {
+    let old = merge_edits(old)?;
+    let new = merge_edits(new)?;
+    // Position in the changed source code of the `pos` in the original one. The position is never
+    // inside of an old edit, because the old edits are nested into the new ones.
+    let rebase = |pos: usize| -> usize {
+        let shift: isize = old
+            .iter()
+            .filter(|e| e.end_byte <= pos)
+            .map(|e| (e.end_byte - e.start_byte) as isize - e.text.len() as isize)
+            .sum();
+        (pos as isize - shift) as usize
+    };
+    Ok(new
+        .into_iter()
+        .filter(|e| !old.contains(e))
+        .map(|e| Edit {
+            start_byte: rebase(e.start_byte),
+            end_byte: rebase(e.end_byte),
+            ..e
+        })
+        .collect())
+}
+
+/// Returns the edit that changes the `old` source code to the `new` one replacing the text
+/// between their common prefix and suffix.
+pub fn diff_edit(old: &str, new: &str) -> Edit {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(std::cmp::min(old.len(), new.len()), |((i, _), _)| i);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    Edit {
+        start_byte: prefix,
+        end_byte: old.len() - suffix,
+        text: new[prefix..new.len() - suffix].to_string(),
+        kind: "text".to_string(),
+    }
+}
+
+/// Kinds of the Lua expressions that may be simplified. The expressions in parentheses are not
+/// separate nodes: the parentheses are the anonymous siblings of the expression.
+const EXPRESSIONS: &[&str] = &[
+    "binary_operation",
+    "unary_operation",
+    "function_call",
+    "function_definition",
+    "table_constructor",
+    "string",
+    "number",
+];
+
 /// Returns source code of the given node. For debugging purposes.
 #[allow(dead_code)]
 pub fn node_source(source: &str, node: &TSNode<'_>) -> String {
@@ -128,84 +387,159 @@ impl Parser for Lua {
             .to_string()
     }
 
-    fn remove_nodes<'a>(&self, source_code: &str, nodes: &[TSNode<'a>]) -> Result<String, String> {
-        // Incrementally parse an AST for the given source code. It will contain positions for the
-        // nodes we'll remove.
-        let mut parser = TSParser::new();
-        parser.set_language(self.language).unwrap();
-        let current_tree = match parser.parse(source_code, Some(&self.tree)) {
-            Some(tree) => tree,
-            None => return Err("Cannot parse the given source".to_string()),
-        };
+    fn functions_query(&self) -> String {
+        "[(function_declaration) (function_definition)] @function".to_string()
+    }
 
-        // TODO: This hack should be replaced with incremental editing ASAP. But to keep parser
-        // object immutable, we have to match nodes from the original tree to the current tree to
-        // get correct offsets and positions in bytes.
-        let mut new_nodes: HashMap<String, TSNode<'_>> = HashMap::new();
-        let mut cursor = current_tree.walk();
-        for node in current_tree.root_node().children(&mut cursor) {
-            new_nodes.insert(node_source(&self.original_source, &node), node);
-        }
+    fn function_body<'a>(&self, node: TSNode<'a>) -> Option<TSNode<'a>> {
+        node.child_by_field_name("body")
+    }
 
-        let nodes_to_remove: HashSet<TSNode<'a>> = HashSet::from_iter(nodes.iter().cloned());
-        let mut removed_ranges: HashSet<(usize, usize)> = HashSet::new();
-        for node in self.tree.root_node().children(&mut self.tree.walk()) {
-            if !nodes_to_remove.contains(&node) {
-                continue;
-            }
-            // TODO: We could use tree-sitter edits to implement the incremental parsing and make
-            // it faster. But this will require changes in the parser to make it stateful.
-            // edits.push(TSInputEdit {
-            //     start_byte: node.start_byte(),
-            //     old_end_byte: node.end_byte(),
-            //     new_end_byte: node.start_byte(),
-            //     start_position: node.start_position(),
-            //     old_end_position: node.end_position(),
-            //     new_end_position: node.start_position(),
-            // });
-            let new_node = match new_nodes.get(&node_source(&self.original_source, &node)) {
-                Some(node) => node,
-                None => {
-                    log::error!(
-                        "Cannot find:\n  '{}'",
-                        &node_source(&self.original_source, &node)
-                    );
-                    log::error!("Possible values:");
-                    for k in new_nodes.into_keys() {
-                        log::error!("  '{}'", k);
-                    }
-                    log::error!("Source code:\n{}", source_code);
-                    panic!("Internal error")
-                }
+    fn conditionals_query(&self) -> String {
+        "(if_statement) @if".to_string()
+    }
+
+    fn branches<'a>(&self, node: TSNode<'a>) -> Vec<Branch<'a>> {
+        let mut branches = vec![Branch {
+            clause: None,
+            condition: node.child_by_field_name("condition"),
+            body: node.child_by_field_name("consequence"),
+        }];
+        let mut cursor = node.walk();
+        for clause in node.named_children(&mut cursor) {
+            let body = match clause.kind() {
+                "elseif_statement" => "consequence",
+                "else_statement" => "body",
+                _ => continue,
             };
-            removed_ranges.insert((new_node.start_byte(), new_node.end_byte()));
+            branches.push(Branch {
+                clause: Some(clause),
+                condition: clause.child_by_field_name("condition"),
+                body: clause.child_by_field_name(body),
+            });
         }
+        branches
+    }
 
-        // Sort removed ranges in descending order, because we will remove symbols from the end to
-        // don't break the previous positions.
-        let mut removed_ranges = removed_ranges.into_iter().collect::<Vec<(usize, usize)>>();
-        removed_ranges.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    fn loops_query(&self) -> String {
+        "[(for_statement) (while_statement) (repeat_statement)] @loop".to_string()
+    }
 
-        // Remove removed ranges from the program code.
-        let mut source: Vec<u8> = source_code.as_bytes().to_vec();
-        let source = match std::str::from_utf8(
-            removed_ranges
-                .iter()
-                .fold(&mut source, |source_bytes, &range| {
-                    source_bytes.drain(range.0..range.1);
-                    source_bytes
-                })
-                .as_slice(),
-        ) {
-            Ok(v) => v.to_string(),
-            Err(err) => {
-                return Err(format!(
-                    "Invalid UTF-8 sequence in the source code: {}",
-                    err
-                ))
+    fn loop_parts<'a>(&self, node: TSNode<'a>) -> LoopParts<'a> {
+        let mut parts = LoopParts {
+            body: node.child_by_field_name("body"),
+            variable: None,
+            bounds: None,
+            generic: None,
+        };
+        if let Some(clause) = node.child_by_field_name("clause") {
+            match clause.kind() {
+                "for_numeric_clause" => {
+                    let last = clause
+                        .child_by_field_name("step")
+                        .or_else(|| clause.child_by_field_name("end"));
+                    parts.variable = clause.child_by_field_name("name");
+                    parts.bounds = clause.child_by_field_name("start").zip(last);
+                }
+                "for_generic_clause" => {
+                    parts.generic = clause.named_child(0).zip(clause.named_child(1))
+                }
+                _ => (),
+            }
+        }
+        parts
+    }
+
+    fn is_expression(&self, node: &TSNode<'_>) -> bool {
+        if !EXPRESSIONS.contains(&node.kind()) {
+            return false;
+        }
+        match node.parent() {
+            Some(parent) if self.is_statement_block(&parent) => false,
+            Some(parent) if parent.kind() == "variable_declarator" => false,
+            Some(parent) if parent.kind() == "function_call" => {
+                parent.child_by_field_name("prefix") != Some(*node)
             }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    fn operands<'a>(&self, node: TSNode<'a>) -> Vec<TSNode<'a>> {
+        let parent = match node.kind() {
+            "function_call" => match node.child_by_field_name("args") {
+                Some(args) if args.kind() == "function_arguments" => args,
+                // The only argument is a string or a table constructor without parentheses.
+                Some(args) => return vec![args],
+                None => return vec![],
+            },
+            _ => node,
         };
+        let mut cursor = parent.walk();
+        let operands = parent
+            .named_children(&mut cursor)
+            .filter(|c| EXPRESSIONS.contains(&c.kind()) || c.kind() == "identifier")
+            .collect::<Vec<_>>();
+        operands
+    }
 
-        Ok(source)
+    fn expression_range(&self, node: &TSNode<'_>) -> std::ops::Range<usize> {
+        let is_token = |n: &TSNode, kind: &str| !n.is_named() && n.kind() == kind;
+        // The outer parentheses of the arguments belong to the call.
+        let in_arguments = node
+            .parent()
+            .is_some_and(|p| p.kind() == "function_arguments");
+        match (node.prev_sibling(), node.next_sibling()) {
+            (Some(prev), Some(next))
+                if is_token(&prev, "(")
+                    && is_token(&next, ")")
+                    && !(in_arguments && prev.prev_sibling().is_none()) =>
+            {
+                prev.start_byte()..next.end_byte()
+            }
+            _ => node.byte_range(),
+        }
+    }
+
+    fn is_block(&self, node: &TSNode<'_>) -> bool {
+        matches!(
+            node.kind(),
+            "program" | "block" | "function_body" | "table_constructor" | "function_arguments"
+        )
+    }
+
+    fn is_statement_block(&self, node: &TSNode<'_>) -> bool {
+        matches!(node.kind(), "program" | "block" | "function_body")
+    }
+
+    fn is_separator(&self, node: &TSNode<'_>) -> bool {
+        !node.is_named() && matches!(node.kind(), "," | ";")
+    }
+
+    fn source(&self) -> &str {
+        &self.original_source
+    }
+}
+
+/// Returns the number of the statements in the Lua `source`, i.e. the removable children of the
+/// blocks except comments. Returns zero if the source cannot be parsed.
+pub fn count_statements(source: &str) -> usize {
+    let lua = match Lua::new(source) {
+        Ok(lua) => lua,
+        Err(_) => return 0,
+    };
+    let mut count = 0;
+    let mut nodes = vec![lua.ast_root()];
+    while let Some(node) = nodes.pop() {
+        if lua.is_statement_block(&node) {
+            count += lua
+                .removable_children(node)
+                .iter()
+                .filter(|c| c.kind() != "comment")
+                .count();
+        }
+        let mut cursor = node.walk();
+        nodes.extend(node.named_children(&mut cursor));
     }
+    count
 }
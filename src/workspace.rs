@@ -0,0 +1,340 @@
+//! Workspace keeps the current state of the reduced program and writes candidates to disk.
+//!
+//! In the default mode the workspace is a single Lua file. In the recursive mode it contains all
+//! the files of the directory that contains the given Lua file: the Lua files are kept in memory,
+//! because they are reduced, and the rest of the files are copied to each candidate as is.
+use crate::app::{backup_path, App};
+use crate::cache::Hasher;
+use crate::error::Error;
+use crate::progress::Size;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Workspace {
+    /// Directory that contains the reduced project in the recursive mode.
+    root: Option<PathBuf>,
+    /// Directory created by `dd`. It is never copied to the candidates.
+    output_dir: PathBuf,
+    /// Current source code of the Lua files. The keys are paths relative to the `root` in the
+    /// recursive mode and the canonical path to the tested file otherwise.
+    files: BTreeMap<String, String>,
+    /// File that contains the entry point of the program.
+    main: String,
+    /// File that is reduced at the moment.
+    target: String,
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> Error {
+    Error::new(format!("{}: {}", path.display(), err))
+}
+
+fn is_lua(path: &Path) -> bool {
+    path.extension() == Some(std::ffi::OsStr::new("lua"))
+}
+
+/// Collects paths to all the files in the `dir` excluding the `skip` directory.
+fn walk(dir: &Path, skip: &Path, acc: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(|err| io_error(dir, err))? {
+        let path = entry.map_err(|err| io_error(dir, err))?.path();
+        if path == skip {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, skip, acc)?;
+        } else {
+            acc.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns canonical form of the `path` if it exists.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns `path` relative to `root` using `/` as a separator.
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Writes `content` to `path` creating the parent directories.
+fn write_file(path: &Path, content: &str) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| io_error(parent, err))?;
+    }
+    fs::write(path, content).map_err(|err| io_error(path, err))
+}
+
+impl Workspace {
+    pub fn from_app(app: &App) -> Result<Self, Error> {
+        let output_dir = PathBuf::from(&app.output_dir);
+        let file = PathBuf::from(&app.file);
+        if !app.recursive {
+            let source = fs::read_to_string(&file).map_err(|err| io_error(&file, err))?;
+            let key = canonical(&file).to_string_lossy().into_owned();
+            return Ok(Workspace {
+                root: None,
+                output_dir,
+                files: BTreeMap::from([(key.clone(), source)]),
+                main: key.clone(),
+                target: key,
+            });
+        }
+
+        let file = fs::canonicalize(&file).map_err(|err| io_error(&file, err))?;
+        let root = match file.parent() {
+            Some(root) => root.to_path_buf(),
+            None => return Err(Error::new("Cannot get the directory of the Lua file")),
+        };
+        let mut paths = vec![];
+        walk(&root, &canonical(&output_dir), &mut paths)?;
+        let mut files = BTreeMap::new();
+        for path in paths.iter().filter(|p| is_lua(p)) {
+            let source = fs::read_to_string(path).map_err(|err| io_error(path, err))?;
+            files.insert(relative(&root, path), source);
+        }
+        let main = relative(&root, &file);
+        Ok(Workspace {
+            root: Some(root),
+            output_dir,
+            files,
+            main: main.clone(),
+            target: main,
+        })
+    }
+
+    /// Creates the workspace of the reduction configured in the `app` that contains the given Lua
+    /// `files`, e.g. the original ones saved in the trace. The Lua files are never read from disk.
+    pub fn from_files(app: &App, files: BTreeMap<String, String>) -> Result<Self, Error> {
+        let file = PathBuf::from(&app.file);
+        let (root, main) = if app.recursive {
+            let file = canonical(&std::env::current_dir().unwrap_or_default().join(&file));
+            match file.parent() {
+                Some(root) => (Some(root.to_path_buf()), relative(root, &file)),
+                None => return Err(Error::new("Cannot get the directory of the Lua file")),
+            }
+        } else {
+            match files.keys().next() {
+                Some(key) => (None, key.clone()),
+                None => return Err(Error::new("No Lua files are given")),
+            }
+        };
+        if !files.contains_key(&main) {
+            return Err(Error::new(format!("The files don't contain '{}'", main)));
+        }
+        Ok(Workspace {
+            root,
+            output_dir: PathBuf::from(&app.output_dir),
+            files,
+            main: main.clone(),
+            target: main,
+        })
+    }
+
+    /// Creates the workspace of a single Lua `file` that contains the `source`. The file is never
+    /// read from disk.
+    pub fn from_source(file: &str, source: &str) -> Self {
+        Workspace {
+            root: None,
+            output_dir: PathBuf::new(),
+            files: BTreeMap::from([(file.to_string(), source.to_string())]),
+            main: file.to_string(),
+            target: file.to_string(),
+        }
+    }
+
+    /// Returns true if the workspace contains the whole directory.
+    pub fn is_project(&self) -> bool {
+        self.root.is_some()
+    }
+
+    /// Returns the Lua files in the order they should be reduced: the main file goes first.
+    pub fn lua_files(&self) -> Vec<String> {
+        let mut files = vec![self.main.clone()];
+        files.extend(self.files.keys().filter(|f| **f != self.main).cloned());
+        files
+    }
+
+    pub fn contains(&self, file: &str) -> bool {
+        self.files.contains_key(file)
+    }
+
+    pub fn main(&self) -> &str {
+        &self.main
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the key of the Lua `file` saved by a previous run. In the single-file mode the key
+    /// is the canonical path, so the file is found however its path is written.
+    pub fn key(&self, file: &str) -> String {
+        match &self.root {
+            Some(_) => file.to_string(),
+            None => canonical(Path::new(file)).to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Returns the total size of the Lua files in bytes.
+    pub fn size(&self) -> usize {
+        self.files.values().map(|s| s.len()).sum()
+    }
+
+    /// Measures the Lua files of the program.
+    pub fn measure(&self) -> Size {
+        self.files.values().map(|s| Size::of(s)).sum()
+    }
+
+    /// Sets the Lua file which will be reduced by the passes.
+    pub fn set_target(&mut self, file: &str) {
+        self.target = file.to_string();
+    }
+
+    /// Returns the current source code of the reduced file.
+    pub fn target_source(&self) -> String {
+        self.files[&self.target].clone()
+    }
+
+    /// Returns the current source code of the given Lua file.
+    pub fn source(&self, file: &str) -> Option<&str> {
+        self.files.get(file).map(|s| s.as_str())
+    }
+
+    /// Replaces the source code of the reduced file.
+    pub fn update(&mut self, source: &str) {
+        self.files.insert(self.target.clone(), source.to_string());
+    }
+
+    /// Returns the Lua files of the candidate, where the reduced file contains `source` and the
+    /// `removed` files are missing.
+    pub fn files_with(&self, source: &str, removed: &[String]) -> BTreeMap<String, String> {
+        let mut files = self.files.clone();
+        files.insert(self.target.clone(), source.to_string());
+        files.retain(|file, _| !removed.contains(file));
+        files
+    }
+
+    /// Replaces the Lua files, e.g. with the ones restored from the checkpoint. Returns an error
+    /// if the reduced file is missing in them.
+    pub fn restore(&mut self, files: BTreeMap<String, String>) -> Result<(), Error> {
+        let files = files
+            .into_iter()
+            .map(|(file, source)| (self.key(&file), source))
+            .collect::<BTreeMap<_, _>>();
+        if !files.contains_key(&self.target) {
+            return Err(Error::new(format!(
+                "The restored files don't contain '{}'",
+                self.target
+            )));
+        }
+        self.files = files;
+        Ok(())
+    }
+
+    /// Removes the given Lua files from the project.
+    pub fn remove_files(&mut self, files: &[String]) {
+        for f in files.iter() {
+            self.files.remove(f);
+        }
+    }
+
+    /// Writes a candidate to `path`. The candidate is the current state of the workspace, where
+    /// the reduced file contains `source` and the `removed` files are missing. Returns path that
+    /// should be given to the script: the Lua file or the directory in the recursive mode.
+    pub fn write(&self, path: &str, source: &str, removed: &[String]) -> Result<String, Error> {
+        let root = match &self.root {
+            Some(root) => root,
+            None => {
+                write_file(Path::new(path), source)?;
+                return Ok(path.to_string());
+            }
+        };
+        let dir = PathBuf::from(path);
+        let mut paths = vec![];
+        // The rest of the files are missing when a trace is replayed without the project.
+        if root.is_dir() {
+            walk(root, &canonical(&self.output_dir), &mut paths)?;
+        }
+        for p in paths.iter().filter(|p| !is_lua(p)) {
+            let dest = dir.join(relative(root, p));
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).map_err(|err| io_error(parent, err))?;
+            }
+            fs::copy(p, &dest).map_err(|err| io_error(&dest, err))?;
+        }
+        for (file, content) in self.files.iter() {
+            if removed.contains(file) {
+                continue;
+            }
+            let content = if *file == self.target {
+                source
+            } else {
+                content
+            };
+            write_file(&dir.join(file), content)?;
+        }
+        Ok(path.to_string())
+    }
+
+    /// Feeds the content of the candidate to the `hasher`. The candidate is the same as the one
+    /// created by the `write` method.
+    pub fn hash(&self, hasher: &mut Hasher, source: &str, removed: &[String]) {
+        if self.root.is_none() {
+            hasher.write_str(source);
+            return;
+        }
+        for (file, content) in self.files.iter() {
+            if removed.contains(file) {
+                continue;
+            }
+            hasher.write_str(file);
+            hasher.write_str(if *file == self.target {
+                source
+            } else {
+                content
+            });
+        }
+    }
+
+    /// Writes the current state of the workspace to `path`.
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        self.write(path, &self.target_source(), &[])?;
+        Ok(())
+    }
+
+    /// Replaces the original files with their reduced versions. A backup is created for every
+    /// modified or removed file.
+    pub fn save_in_place(&self) -> Result<(), Error> {
+        let mut originals = vec![];
+        match &self.root {
+            Some(root) => walk(root, &canonical(&self.output_dir), &mut originals)?,
+            None => originals.push(PathBuf::from(&self.main)),
+        }
+        for path in originals.iter().filter(|p| is_lua(p) || !self.is_project()) {
+            let file = match &self.root {
+                Some(root) => relative(root, path),
+                None => self.main.clone(),
+            };
+            let original = fs::read_to_string(path).map_err(|err| io_error(path, err))?;
+            let reduced = self.files.get(&file);
+            if reduced == Some(&original) {
+                continue;
+            }
+            let backup = PathBuf::from(backup_path(&path.to_string_lossy()));
+            fs::copy(path, &backup).map_err(|err| io_error(&backup, err))?;
+            match reduced {
+                Some(source) => fs::write(path, source).map_err(|err| io_error(path, err))?,
+                None => fs::remove_file(path).map_err(|err| io_error(path, err))?,
+            }
+        }
+        Ok(())
+    }
+}
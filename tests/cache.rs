@@ -0,0 +1,17 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn persistent_cache_1() {
+    Test::new()
+        .source(
+            "
+function foo()  assert(false) end
+function bar()  return false  end
+function main() foo() end
+",
+        )
+        .passes("top")
+        .check_cached_rerun("! grep -q -E \"assert\\(false\\)\" $1");
+}
@@ -1,7 +1,10 @@
 use dd::app::parse_passes;
 use dd::app::App;
+use dd::checkpoint::{Checkpoint, DdminState};
+use dd::config::Config;
 use dd::driver::run_app;
 use dd::error::Error;
+use dd::progress::Size;
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use tempfile::{tempdir, TempDir};
@@ -44,24 +47,178 @@ impl Test {
         self
     }
 
+    /// Applies the configuration file with the given content. Relative paths in it are resolved
+    /// against the directory of the tested file.
+    #[allow(dead_code)]
+    pub(crate) fn config(mut self, content: &str) -> Self {
+        let path = self.file_tempdir.path().join("dd.toml");
+        fs::write(&path, content).unwrap();
+        Config::load(&path.display().to_string())
+            .unwrap()
+            .apply(&mut self.app)
+            .unwrap();
+        self
+    }
+
+    /// Uses the command line instead of the script.
+    #[allow(dead_code)]
+    pub(crate) fn command(mut self, command: &str) -> Self {
+        self.app.script = command.to_string();
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn exit_codes(mut self, codes: &[i32]) -> Self {
+        self.app.exit_codes = codes.to_vec();
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn signal(mut self, signal: i32) -> Self {
+        self.app.signal = Some(signal);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn stdout_not_regex(mut self, regex: &str) -> Self {
+        self.app.stdout_not_regex = Some(regex.to_string());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn stderr_regex(mut self, regex: &str) -> Self {
+        self.app.stderr_regex = Some(regex.to_string());
+        self
+    }
+
+    #[allow(dead_code)]
     pub(crate) fn source(mut self, source: &str) -> Self {
         self.app.file = self.file_tempdir.path().join("in").display().to_string();
         fs::write(&self.app.file, source).unwrap();
         self
     }
 
+    /// Creates a project that contains the given Lua files and enables the recursive mode. The
+    /// first file is the main one.
+    #[allow(dead_code)]
+    pub(crate) fn project(mut self, files: &[(&str, &str)]) -> Self {
+        let dir = self.file_tempdir.path().join("project");
+        for (name, source) in files.iter() {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        self.app.file = dir.join(files[0].0).display().to_string();
+        self.app.recursive = true;
+        self
+    }
+
+    /// Resumes the reduction from the checkpoint where the `top` pass has reduced the tested file
+    /// to the given `source`. The original program is the one given in `source`.
+    #[allow(dead_code)]
+    pub(crate) fn resume_from(mut self, source: &str) -> Self {
+        let checkpoint = Checkpoint {
+            round: 0,
+            reproduced: true,
+            pass: Some("top".to_string()),
+            target: Some(self.app.file.clone()),
+            initial: Some(Size::of(&fs::read_to_string(&self.app.file).unwrap())),
+            ddmin: Some(DdminState {
+                pass: "Top".to_string(),
+                target: self.app.file.clone(),
+                sequence: 0,
+                length: source.lines().filter(|l| !l.is_empty()).count(),
+                granularity: 2,
+                position: 0,
+            }),
+            files: [(self.app.file.clone(), source.to_string())].into(),
+        };
+        fs::create_dir_all(&self.app.output_dir).unwrap();
+        checkpoint.save(&self.app.checkpoint_file()).unwrap();
+        self.app.resume = true;
+        self
+    }
+
+    /// Refers to the tested file by another path, e.g. the one typed differently on `--resume`.
+    #[allow(dead_code)]
+    pub(crate) fn file_alias(mut self) -> Self {
+        self.app.file = self
+            .file_tempdir
+            .path()
+            .join(".")
+            .join("in")
+            .display()
+            .to_string();
+        self
+    }
+
     #[allow(dead_code)]
     pub(crate) fn timeout(mut self, timeout: u32) -> Self {
         self.app.timeout = Some(timeout);
         self
     }
 
+    #[allow(dead_code)]
+    pub(crate) fn timeout_interesting(mut self) -> Self {
+        self.app.timeout_interesting = true;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn check_empty(mut self) -> Self {
+        self.app.check_empty = true;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn retries(mut self, retries: usize) -> Self {
+        self.app.retries = retries;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn confirm(mut self, confirm: usize) -> Self {
+        self.app.confirm = confirm;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn result(mut self) -> Self {
+        self.app.result = Some(self.file_tempdir.path().join("out").display().to_string());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn in_place(mut self) -> Self {
+        self.app.in_place = true;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn max_rounds(mut self, rounds: usize) -> Self {
+        self.app.max_rounds = Some(rounds);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn jobs(mut self, jobs: usize) -> Self {
+        self.app.jobs = jobs;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn no_subsets(mut self) -> Self {
+        self.app.subsets = false;
+        self
+    }
+
+    #[allow(dead_code)]
     pub(crate) fn passes(mut self, passes_config: &str) -> Self {
         self.app.passes = parse_passes(Some(passes_config)).unwrap();
         self
     }
 
-    fn run(self) -> Result<String, Error> {
+    fn run(&self) -> Result<String, Error> {
         run_app(&self.app)
     }
 
@@ -73,12 +230,161 @@ impl Test {
         }
     }
 
+    /// Checks that the reduced program is written to all the configured locations.
     #[allow(dead_code)]
-    pub(crate) fn check_not_reduced(self) {
+    pub(crate) fn check_saved(self, expected: &str) {
+        let best_file = self.app.best_file();
+        let backup_file = self.app.backup_file();
+        let result = self.app.result.clone();
+        let in_place = self.app.in_place;
+        let file = self.app.file.clone();
+        let original = fs::read_to_string(&file).unwrap();
+        let expected = expected.replace("\n", "");
+        let read = |path: &str| fs::read_to_string(path).unwrap().replace("\n", "");
+        if let Err(err) = self.run() {
+            panic!("Error while running the test: {}", err)
+        }
+        assert_eq!(read(&best_file), expected);
+        if let Some(result) = &result {
+            assert_eq!(read(result), expected);
+        }
+        if in_place {
+            assert_eq!(read(&file), expected);
+            assert_eq!(fs::read_to_string(&backup_file).unwrap(), original);
+        }
+    }
+
+    /// Checks the files of the reduced project. `None` means that the file has been removed.
+    #[allow(dead_code)]
+    pub(crate) fn check_reduced_project(mut self, expected: &[(&str, Option<&str>)]) {
+        let result = self.file_tempdir.path().join("out");
+        self.app.result = Some(result.display().to_string());
+        if let Err(err) = self.run() {
+            panic!("Error while running the test: {}", err)
+        }
+        for (name, source) in expected.iter() {
+            let got = fs::read_to_string(result.join(name)).ok();
+            assert_eq!(
+                got.map(|s| s.replace("\n", "")),
+                source.map(|s| s.replace("\n", "")),
+                "{}",
+                name
+            );
+        }
+    }
+
+    /// Checks that the second run of the same reduction with the persistent cache doesn't execute
+    /// the check script.
+    #[allow(dead_code)]
+    pub(crate) fn check_cached_rerun(self, script: &str) {
+        let log = self.file_tempdir.path().join("log");
+        let mut test = self.script(&format!("echo >> {}\n{}", log.display(), script));
+        test.app.persist_cache = true;
+        let first = run_app(&test.app).unwrap();
+        assert!(log.exists());
+        fs::remove_file(&log).unwrap();
+        let second = run_app(&test.app).unwrap();
+        assert_eq!(first, second);
+        assert!(!log.exists());
+    }
+
+    /// Checks that the interrupted reduction saves the smallest failing program found so far and
+    /// the summary to the output directory.
+    #[allow(dead_code)]
+    pub(crate) fn check_interrupted(self, expected_best: &str) {
+        dd::interrupt::install_handler().unwrap();
+        let best_file = self.app.best_file();
+        let summary_file = self.app.summary_file();
         match self.run() {
-            Err(Error::NoChange) => (),
+            Err(Error::Interrupted) => (),
             Err(err) => panic!("Error while running the test: {}", err),
-            Ok(src) => panic!("Source code has been reduced: {}", src),
+            Ok(src) => panic!("The reduction has not been interrupted: {}", src),
+        }
+        assert_eq!(
+            fs::read_to_string(&best_file).unwrap().replace("\n", ""),
+            expected_best.replace("\n", "")
+        );
+        assert!(fs::read_to_string(&summary_file)
+            .unwrap()
+            .contains("Status: interrupted"));
+    }
+
+    /// Checks that the summary written to the output directory contains the given lines.
+    #[allow(dead_code)]
+    pub(crate) fn check_summary(self, expected: &[&str]) {
+        let summary_file = self.app.summary_file();
+        if let Err(err) = self.run() {
+            panic!("Error while running the test: {}", err)
+        }
+        let summary = fs::read_to_string(&summary_file).unwrap();
+        for line in expected.iter() {
+            assert!(
+                summary.lines().any(|l| l.starts_with(line)),
+                "{}\n{}",
+                line,
+                summary
+            );
+        }
+    }
+
+    /// Checks that the events of the given types are written in the given order. Returns the
+    /// written events.
+    #[allow(dead_code)]
+    pub(crate) fn check_events(mut self, expected: &[&str]) -> Vec<serde_json::Value> {
+        let path = self.file_tempdir.path().join("events.jsonl");
+        self.app.events = Some(path.display().to_string());
+        if let Err(err) = run_app(&self.app) {
+            panic!("Error while running the test: {}", err)
+        }
+        let events = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        let mut types = events.iter().map(|e| e["event"].as_str().unwrap());
+        for event in expected.iter() {
+            assert!(types.any(|t| t == *event), "{} in {:?}", event, events);
+        }
+        events
+    }
+
+    /// Checks that the replay of the trace gives the same program as the reduction. The original
+    /// program is deleted before the replay, so it uses only the trace.
+    #[allow(dead_code)]
+    pub(crate) fn check_replayed(self, expected: &str) {
+        let trace_file = self.app.trace_file();
+        let reduced = match self.run() {
+            Ok(source) => source,
+            Err(err) => panic!("Error while running the test: {}", err),
+        };
+        assert_eq!(reduced.replace("\n", ""), expected.replace("\n", ""));
+        if self.app.recursive {
+            fs::remove_dir_all(self.file_tempdir.path().join("project")).unwrap();
+        } else {
+            fs::remove_file(&self.app.file).unwrap();
+        }
+        match dd::trace::replay(&trace_file) {
+            Ok(replayed) => assert_eq!(replayed, reduced),
+            Err(err) => panic!("Error while replaying the trace: {}", err),
+        }
+    }
+
+    /// Checks that the reduction is rejected because the original input doesn't reproduce the
+    /// failure.
+    #[allow(dead_code)]
+    pub(crate) fn check_not_interesting(self) {
+        match self.run_error() {
+            Error::NotInteresting(_) => (),
+            err => panic!("Error while running the test: {}", err),
+        }
+    }
+
+    /// Returns the error that stopped the reduction.
+    #[allow(dead_code)]
+    pub(crate) fn run_error(self) -> Error {
+        match self.run() {
+            Err(err) => err,
+            Ok(src) => panic!("The reduction has not failed: {}", src),
         }
     }
 }
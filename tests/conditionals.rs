@@ -0,0 +1,57 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn lua_conditionals_hoist_1() {
+    Test::new()
+        .source(
+            "
+if x > 1 then
+  print(1)
+elseif y then
+  assert(false)
+else
+  print(2)
+end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("conditionals")
+        .check_reduced("assert(false)");
+}
+
+#[test]
+fn lua_conditionals_condition_1() {
+    Test::new()
+        .source(
+            "
+if x > 1 then
+  assert(false)
+else
+  print(2)
+end
+",
+        )
+        .script("! (grep -q -E \"assert\\(false\\)\" $1 && grep -q 'if' $1)")
+        .passes("conditionals")
+        .check_reduced("if true then  assert(false)end");
+}
+
+#[test]
+fn lua_conditionals_short_condition_1() {
+    // The condition is shorter than `true`, but it is replaced as it is a variable.
+    Test::new()
+        .source(
+            "
+if x then
+  assert(false)
+else
+  print(2)
+end
+",
+        )
+        .script("! (grep -q -E \"assert\\(false\\)\" $1 && grep -q 'if' $1)")
+        .passes("conditionals")
+        .check_reduced("if true then  assert(false)end");
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use crate::common::Test;
+use dd::app::App;
+use dd::config::Config;
+
+#[test]
+fn config_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+error(\"attempt to index\")
+local b = 2
+",
+        )
+        .config(
+            "
+script = \"sh -c 'grep error {} >&2'\"
+stderr-regex = \"attempt to index\"
+passes = [\"top\"]
+",
+        )
+        .check_reduced("error(\"attempt to index\")");
+}
+
+#[test]
+fn config_dump_1() {
+    let mut app = App::new();
+    app.script = "lua {}".to_string();
+    app.timeout = Some(10);
+    app.exit_codes = vec![1, 2];
+    app.passes = dd::app::parse_passes(Some("top,hdd:max-depth=3")).unwrap();
+    let dumped = Config::from_app(&app).to_toml().unwrap();
+    let mut loaded = App::new();
+    toml_roundtrip(&dumped, &mut loaded);
+    assert_eq!(loaded.script, app.script);
+    assert_eq!(loaded.timeout, app.timeout);
+    assert_eq!(loaded.exit_codes, app.exit_codes);
+    assert_eq!(loaded.passes, app.passes);
+}
+
+fn toml_roundtrip(content: &str, app: &mut App) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dd.toml");
+    std::fs::write(&path, content).unwrap();
+    Config::load(&path.display().to_string())
+        .unwrap()
+        .apply(app)
+        .unwrap();
+}
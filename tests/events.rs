@@ -0,0 +1,32 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn events_1() {
+    let events = Test::new()
+        .source(
+            "
+local a = 1
+assert(false)
+",
+        )
+        .script("! grep -q -F 'assert(false)' $1")
+        .passes("top")
+        .check_events(&[
+            "pass_started",
+            "candidate_tested",
+            "candidate_accepted",
+            "pass_finished",
+            "result",
+        ]);
+    // The accepted candidate is one of the tested ones.
+    let accepted = events
+        .iter()
+        .find(|e| e["event"] == "candidate_accepted")
+        .unwrap();
+    assert!(events.iter().any(|e| e["event"] == "candidate_tested"
+        && e["id"] == accepted["id"]
+        && e["outcome"] == "fail"));
+    assert_eq!(events.last().unwrap()["status"], "finished");
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn lua_expressions_1() {
+    Test::new()
+        .source("assert(check(n * 2) and (x + y) > 0)\n")
+        .script("! grep -q -F 'check(' $1")
+        .passes("expressions")
+        .check_reduced("assert(check(n))");
+}
+
+#[test]
+fn lua_expressions_operand_1() {
+    Test::new()
+        .source("print((value + 1) * 2)\n")
+        .script("! grep -q -F 'value' $1")
+        .passes("expressions")
+        .check_reduced("print(value)");
+}
+
+#[test]
+fn lua_expressions_argument_1() {
+    Test::new()
+        .source("local v = outer(inner(1), 2)\n")
+        .script("! grep -q -F 'inner(' $1")
+        .passes("expressions")
+        .check_reduced("local v = inner(1)");
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn skip_unresolved_1() {
+    // The candidates without `print` cannot be tested.
+    Test::new()
+        .source(
+            "
+local a = 1
+print(1)
+assert(false)
+",
+        )
+        .script("grep -q print $1 || exit 125; ! grep -q -F 'assert(false)' $1")
+        .passes("top")
+        .check_reduced("print(1)assert(false)");
+}
+
+#[test]
+fn retries_1() {
+    // Each candidate is unresolved on the first execution.
+    Test::new()
+        .source(
+            "
+local a = 1
+assert(false)
+",
+        )
+        .script("[ -e $1.seen ] || { touch $1.seen; exit 125; }; ! grep -q -F 'assert(false)' $1")
+        .retries(1)
+        .passes("top")
+        .check_reduced("assert(false)");
+}
+
+#[test]
+fn confirm_1() {
+    // The candidates without `keep` reproduce the failure only on the first execution.
+    Test::new()
+        .source(
+            "
+keep()
+print(1)
+assert(false)
+",
+        )
+        .script(
+            "grep -q -F 'assert(false)' $1 || exit 0
+grep -q keep $1 && exit 1
+[ -e $1.seen ] && exit 0
+touch $1.seen
+exit 1",
+        )
+        .confirm(1)
+        .passes("top")
+        .check_reduced("keep()assert(false)");
+}
+
+#[test]
+fn confirm_jobs_1() {
+    // The flaky candidates finish before the failing one, which is checked in the same batch.
+    Test::new()
+        .source(
+            "
+keep()
+print(1)
+assert(false)
+",
+        )
+        .script(
+            "grep -q -F 'assert(false)' $1 || exit 0
+grep -q keep $1 && { sleep 0.2; exit 1; }
+[ -e $1.seen ] && exit 0
+touch $1.seen
+exit 1",
+        )
+        .confirm(1)
+        .jobs(6)
+        .passes("top")
+        .check_reduced("keep()assert(false)");
+}
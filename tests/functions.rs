@@ -0,0 +1,32 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn lua_functions_1() {
+    Test::new()
+        .source(
+            "
+local function unused() print(1) end
+function obj:m() print(2) end
+function main() print(3) assert(false) end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("functions")
+        .check_reduced("function main() print(3) assert(false) end");
+}
+
+#[test]
+fn lua_functions_return_nil_1() {
+    Test::new()
+        .source(
+            "
+local function f() print(1) return 1 end
+assert(f())
+",
+        )
+        .script("! (grep -q 'local function f' $1 && grep -q 'return' $1 && grep -q -F 'assert(f())' $1)")
+        .passes("functions")
+        .check_reduced("local function f() return nil end\nassert(f())");
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn lua_hdd_1() {
+    Test::new()
+        .source(
+            "function main()
+print(1)
+assert(false)
+print(2)
+end",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("hdd")
+        .check_reduced("function main()assert(false)end");
+}
+
+#[test]
+fn lua_hdd_max_depth_1() {
+    Test::new()
+        .source(
+            "print(0)
+function main()
+print(1)
+assert(false)
+end",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("hdd:max-depth=1")
+        .check_reduced("function main()print(1)assert(false)end");
+}
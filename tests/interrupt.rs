@@ -0,0 +1,18 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn interrupt_1() {
+    // The script interrupts dd when it gets a candidate without the failure.
+    Test::new()
+        .source(
+            "
+local a = 1
+print(1)
+",
+        )
+        .script("grep -q print $1 && exit 1; kill -INT $PPID; sleep 10")
+        .passes("top")
+        .check_interrupted("local a = 1print(1)");
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn parallel_top_1() {
+    Test::new()
+        .source(
+            "
+function foo()  assert(false) end
+function bar()  return false  end
+function baz()  assert(false) end
+function main() foo() end
+print(1)
+print(2)
+",
+        )
+        .script("sleep 0.1; ! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("top")
+        .jobs(4)
+        .check_reduced("function foo()  assert(false) end");
+}
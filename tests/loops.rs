@@ -0,0 +1,63 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn lua_loops_unwrap_1() {
+    Test::new()
+        .source(
+            "
+while x do
+  assert(false)
+end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("loops")
+        .check_reduced("do\nassert(false)\nend");
+}
+
+#[test]
+fn lua_loops_unwrap_numeric_1() {
+    Test::new()
+        .source(
+            "
+for i = 1, 10 do
+  assert(i)
+end
+",
+        )
+        .script("! grep -q -F 'assert(i)' $1")
+        .passes("loops")
+        .check_reduced("local i = 1\nassert(i)");
+}
+
+#[test]
+fn lua_loops_pairs_1() {
+    Test::new()
+        .source(
+            "
+for k, v in pairs(t) do
+  assert(v)
+end
+",
+        )
+        .script("! grep -q -F 'assert(v)' $1")
+        .passes("loops")
+        .check_reduced("local k, v = next(t)\nassert(v)");
+}
+
+#[test]
+fn lua_loops_single_iteration_1() {
+    Test::new()
+        .source(
+            "
+for i = 1, 10, 2 do
+  assert(i)
+end
+",
+        )
+        .script("! grep -q -F 'for i = 1' $1")
+        .passes("loops")
+        .check_reduced("for i = 1, 1 do  assert(i)end");
+}
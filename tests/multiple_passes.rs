@@ -18,3 +18,45 @@ function test() assert(false) end
         .passes("top;imports")
         .check_reduced("require(\"mod1\")");
 }
+
+#[test]
+fn max_rounds_1() {
+    Test::new()
+        .source(
+            "
+require(\"mod1\")
+function main() print(\"test\") end
+function test() assert(false) end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("imports;top;hdd")
+        .max_rounds(1)
+        .check_reduced("function test() assert(false) end");
+}
+
+#[test]
+fn skip_rerun_1() {
+    // Only the conditionals pass changes the program in the first round, so the imports pass is
+    // not worth re-running in the second one, unlike the top pass.
+    let events = Test::new()
+        .source(
+            "
+if x then
+  print(1)
+  assert(false)
+end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("imports;top;conditionals")
+        .check_events(&["pass_started", "result"]);
+    let started = |round: u64, pass: &str| {
+        events
+            .iter()
+            .any(|e| e["event"] == "pass_started" && e["round"] == round && e["pass"] == pass)
+    };
+    assert!(started(1, "imports"));
+    assert!(started(2, "top"));
+    assert!(!started(2, "imports"));
+}
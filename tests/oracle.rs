@@ -0,0 +1,86 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn oracle_stderr_regex_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+error(\"attempt to index\")
+local b = 2
+",
+        )
+        .script("grep error $1 >&2; exit 0")
+        .stderr_regex("attempt to index")
+        .passes("top")
+        .check_reduced("error(\"attempt to index\")");
+}
+
+#[test]
+fn oracle_stderr_regex_exit_code_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+error(\"attempt to index\")
+crash()
+local b = 2
+",
+        )
+        .script("grep error $1 >&2; grep -q crash $1 && exit 1; exit 0")
+        .stderr_regex("attempt to index")
+        .exit_codes(&[1])
+        .passes("top")
+        .check_reduced("error(\"attempt to index\")\ncrash()");
+}
+
+#[test]
+fn oracle_command_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+assert(false)
+local b = 2
+",
+        )
+        .command("grep -q -F 'assert(false)' {}")
+        .exit_codes(&[0])
+        .passes("top")
+        .check_reduced("assert(false)");
+}
+
+#[test]
+fn oracle_signal_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+crash()
+local b = 2
+",
+        )
+        .script("grep -q crash $1 && kill -SEGV $$; exit 1")
+        .signal(11)
+        .passes("top")
+        .check_reduced("crash()");
+}
+
+#[test]
+fn oracle_stdout_not_regex_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+assert(false)
+local b = 2
+",
+        )
+        .script("grep -q a $1 || echo empty; grep -q -F 'assert(false)' $1")
+        .exit_codes(&[0])
+        .stdout_not_regex("empty")
+        .passes("top")
+        .check_reduced("assert(false)");
+}
@@ -0,0 +1,18 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn progress_summary_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+print(a)
+assert(false)
+",
+        )
+        .script("! grep -q -F 'assert(false)' $1")
+        .passes("top,hdd")
+        .check_summary(&["Status: finished", "Pass ", "top ", "hdd ", "Total: "]);
+}
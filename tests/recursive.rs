@@ -0,0 +1,28 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn recursive_1() {
+    Test::new()
+        .project(&[
+            ("main.lua", "require(\"mod\")"),
+            (
+                "mod.lua",
+                "
+local M = {}
+function M.f() assert(false) end
+function M.g() return 1 end
+return M
+",
+            ),
+            ("unused.lua", "print(\"unused\")"),
+        ])
+        .script("! grep -q -r -E \"assert\\(false\\)\" $1")
+        .passes("top")
+        .check_reduced_project(&[
+            ("main.lua", Some("require(\"mod\")")),
+            ("mod.lua", Some("function M.f() assert(false) end")),
+            ("unused.lua", None),
+        ]);
+}
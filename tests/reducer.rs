@@ -0,0 +1,46 @@
+use dd::error::Error;
+use dd::reducer::{Outcome, Reducer};
+use std::cell::{Cell, RefCell};
+
+fn has_assert(candidate: &str) -> Outcome {
+    if candidate.contains("assert(false)") {
+        Outcome::Fail
+    } else {
+        Outcome::Pass
+    }
+}
+
+#[test]
+fn reducer_1() {
+    let accepted = RefCell::new(vec![]);
+    let updates = Cell::new(0);
+    let reduction = Reducer::new(
+        "
+function foo()  assert(false) end
+function bar()  return false  end
+function main() foo() end
+",
+        has_assert,
+    )
+    .passes("top")
+    .unwrap()
+    .on_progress(|_| updates.set(updates.get() + 1))
+    .on_accepted(|a| accepted.borrow_mut().push(a.source.to_string()))
+    .run()
+    .unwrap();
+    assert_eq!(reduction.source.trim(), "function foo()  assert(false) end");
+    assert_eq!(
+        accepted.borrow().last().map(|s| s.trim()),
+        Some(reduction.source.trim())
+    );
+    assert!(updates.get() > 0);
+    assert!(reduction.size.bytes < reduction.initial.bytes);
+    assert!(reduction.tested > 0);
+    assert_eq!(reduction.passes[0].name, "top");
+}
+
+#[test]
+fn reducer_not_interesting_1() {
+    let result = Reducer::new("print(1)\n", has_assert).run();
+    assert!(matches!(result, Err(Error::NotInteresting(_))));
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn result_file_1() {
+    Test::new()
+        .source(
+            "
+function foo()  assert(false) end
+function bar()  return false  end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("top")
+        .result()
+        .check_saved("function foo()  assert(false) end");
+}
+
+#[test]
+fn in_place_1() {
+    Test::new()
+        .source(
+            "
+function foo()  assert(false) end
+function bar()  return false  end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("top")
+        .in_place()
+        .check_saved("function foo()  assert(false) end");
+}
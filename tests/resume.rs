@@ -0,0 +1,71 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn resume_1() {
+    // The original program doesn't reproduce the failure, so the reduction succeeds only if it
+    // continues from the checkpoint.
+    Test::new()
+        .source(
+            "
+print(1)
+assert(false)
+",
+        )
+        .resume_from(
+            "
+local resumed = 1
+print(2)
+assert(false)
+",
+        )
+        .script("! (grep -q -E \"assert\\(false\\)\" $1 && grep -q resumed $1)")
+        .passes("top")
+        .check_reduced("local resumed = 1assert(false)");
+}
+
+#[test]
+fn resume_summary_1() {
+    // The size of the original program is kept in the checkpoint.
+    Test::new()
+        .source(
+            "
+print(1)
+assert(false)
+",
+        )
+        .resume_from(
+            "
+local resumed = 1
+print(2)
+assert(false)
+",
+        )
+        .script("! (grep -q -E \"assert\\(false\\)\" $1 && grep -q resumed $1)")
+        .passes("top")
+        .check_summary(&["Status: finished", "Size: 24 -> "]);
+}
+
+#[test]
+fn resume_file_alias_1() {
+    // The tested file is given by another path than the one saved in the checkpoint.
+    Test::new()
+        .source(
+            "
+print(1)
+assert(false)
+",
+        )
+        .resume_from(
+            "
+local resumed = 1
+print(2)
+assert(false)
+",
+        )
+        .file_alias()
+        .script("! (grep -q -E \"assert\\(false\\)\" $1 && grep -q resumed $1)")
+        .passes("top")
+        .check_reduced("local resumed = 1assert(false)");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use crate::common::Test;
+use dd::error::Error;
+
+#[test]
+fn sanity_not_interesting_1() {
+    let err = Test::new()
+        .source("print(1)")
+        .script("echo checked; ! grep -q -F 'assert(false)' $1")
+        .passes("top")
+        .run_error();
+    match err {
+        Error::NotInteresting(report) => assert!(report.contains("checked")),
+        err => panic!("Unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn sanity_always_interesting_1() {
+    let err = Test::new()
+        .source("print(1)\nassert(false)")
+        .script("exit 1")
+        .check_empty()
+        .passes("top")
+        .run_error();
+    assert!(matches!(err, Error::AlwaysInteresting(_)), "{}", err);
+}
+
+#[test]
+fn sanity_oracle_failed_1() {
+    let err = Test::new()
+        .source("print(1)\nassert(false)")
+        .command("/nonexistent/script")
+        .passes("top")
+        .run_error();
+    assert!(matches!(err, Error::OracleFailed(_)), "{}", err);
+}
+
+#[test]
+fn sanity_oracle_failed_2() {
+    // The shell returns 127 when the command is not found.
+    let err = Test::new()
+        .source("print(1)\nassert(false)")
+        .script("nonexistent-command $1")
+        .passes("top")
+        .run_error();
+    assert!(matches!(err, Error::OracleFailed(_)), "{}", err);
+}
+
+#[test]
+fn sanity_not_reducible_1() {
+    // The original input is the result when the passes cannot reduce it.
+    Test::new()
+        .source("assert(false)")
+        .script("! grep -q -F 'assert(false)' $1")
+        .check_empty()
+        .passes("top")
+        .check_saved("assert(false)");
+}
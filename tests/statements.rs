@@ -0,0 +1,24 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn lua_statements_1() {
+    Test::new()
+        .source(
+            "
+function main()
+print(1)
+do
+print(2)
+assert(false)
+print(3)
+end
+print(4)
+end
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("statements")
+        .check_reduced("function main()\ndo\nassert(false)\nend\nend");
+}
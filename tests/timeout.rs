@@ -0,0 +1,37 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn timeout_uninteresting_1() {
+    // The candidates without `fast()` hang, and they must not be accepted.
+    Test::new()
+        .source(
+            "
+slow()
+fast()
+assert(false)
+",
+        )
+        .script("grep -q fast $1 || sleep 10; ! grep -q -E \"assert\\(false\\)\" $1")
+        .timeout(1)
+        .passes("top")
+        .check_reduced("fast()assert(false)");
+}
+
+#[test]
+fn timeout_interesting_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+while true do end
+local b = 2
+",
+        )
+        .script("grep -q while $1 && sleep 10; exit 0")
+        .timeout(1)
+        .timeout_interesting()
+        .passes("top")
+        .check_reduced("while true do end");
+}
@@ -17,3 +17,37 @@ function main() foo() end
         .passes("top")
         .check_reduced("function foo()  assert(false) end");
 }
+
+#[test]
+fn lua_top_duplicates_1() {
+    Test::new()
+        .source(
+            "
+foo()
+foo()
+assert(false)
+foo()
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("top")
+        .check_reduced("assert(false)");
+}
+
+#[test]
+fn lua_top_no_subsets_1() {
+    Test::new()
+        .source(
+            "
+function foo()  assert(false) end
+function bar()  return false  end
+function baz()  return true   end
+function main() foo() end
+print(1)
+",
+        )
+        .script("! grep -q -E \"assert\\(false\\)\" $1")
+        .passes("top")
+        .no_subsets()
+        .check_reduced("function foo()  assert(false) end");
+}
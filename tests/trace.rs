@@ -0,0 +1,33 @@
+mod common;
+
+use crate::common::Test;
+
+#[test]
+fn trace_replay_1() {
+    Test::new()
+        .source(
+            "
+local a = 1
+function f()
+  local b = 2
+  assert(false)
+end
+f()
+",
+        )
+        .script("grep -q -F 'assert(false)' $1 && grep -q -F 'f()' $1 && exit 1; exit 0")
+        .passes("top,hdd")
+        .check_replayed("function f()  assert(false)end");
+}
+
+#[test]
+fn trace_replay_project_1() {
+    Test::new()
+        .project(&[
+            ("main.lua", "require('lib')\nprint(1)\nassert(false)\n"),
+            ("lib.lua", "return {}\n"),
+        ])
+        .script("! grep -q -F 'assert(false)' $1/main.lua")
+        .passes("top")
+        .check_replayed("assert(false)");
+}